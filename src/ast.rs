@@ -1,13 +1,26 @@
-use std::fmt::Debug;
+use std::fmt::{Debug, Display};
+
+use crate::{unit::Unit, value::Value};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Node {
-    Number(f64),
+    Number(Value),
+    Quantity(Value, Unit),
+    Convert(Box<Node>, Unit),
     Expr(Box<Node>, Operator, Box<Node>),
-    Function(String, Box<Node>),
+    Unary(UnaryOperator, Box<Node>),
+    Function(String, Vec<Node>),
     Const(String),
     AssignConst(String, Box<Node>),
-    AssignFunc(String, String, Box<Node>),
+    AssignFunc(String, Vec<String>, Box<Node>),
+    If(Box<Node>, Box<Node>, Box<Node>),
+    Lambda(Vec<String>, Box<Node>),
+    List(Vec<Node>),
+    // rows of a `[1, 2; 3, 4]` literal
+    Matrix(Vec<Vec<Node>>),
+    // inclusive range of integers
+    Range(Box<Node>, Box<Node>),
+    Index(Box<Node>, Box<Node>),
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -17,4 +30,259 @@ pub enum Operator {
     Mult,
     Div,
     Pow,
+    Less,
+    LessEq,
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEq,
+    And,
+    Or,
+    BitAnd,
+    BitOr,
+    Xor,
+    ShiftLeft,
+    ShiftRight,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum UnaryOperator {
+    Plus,
+    Minus,
+    Not,
+    BitNot,
+}
+
+impl Node {
+    // `2m/s` is read as a quantity even where m or s is a variable, this is the product it
+    // stands for when is_name accepts one of its unit names, the other units stay units
+    pub fn quantity_as_product(
+        num: &Value,
+        unit: &Unit,
+        is_name: impl Fn(&str) -> bool,
+    ) -> Option<Node> {
+        let mut product = Node::Number(num.clone());
+        let mut renamed = false;
+        let mut rest = unit.name.as_str();
+        let mut op = Operator::Mult;
+        loop {
+            let end = rest.find(['*', '/']).unwrap_or(rest.len());
+            let (part, tail) = rest.split_at(end);
+            let (name, exp) = part.split_once('^').unwrap_or((part, "1"));
+            let exp: i64 = exp.parse().ok()?;
+
+            let factor = if name
+                .chars()
+                .all(|char| char.is_alphanumeric() || char == '_')
+                && is_name(name)
+            {
+                renamed = true;
+                let name = Node::Const(name.to_owned());
+                match exp {
+                    1 => name,
+                    exp => Node::Expr(
+                        Box::new(name),
+                        Operator::Pow,
+                        Box::new(Node::Number(Value::from(exp))),
+                    ),
+                }
+            } else {
+                Node::Quantity(Value::from(1), Unit::parse(part).ok()?)
+            };
+            product = Node::Expr(Box::new(product), op, Box::new(factor));
+
+            let Some(sep) = tail.chars().next() else {
+                break;
+            };
+            op = if sep == '/' {
+                Operator::Div
+            } else {
+                Operator::Mult
+            };
+            rest = &tail[1..];
+        }
+        renamed.then_some(product)
+    }
+
+    // how tightly the printed form binds, children that bind looser get parentheses
+    fn precedence(&self) -> u8 {
+        match self {
+            Node::AssignConst(..)
+            | Node::AssignFunc(..)
+            | Node::Convert(..)
+            | Node::If(..)
+            | Node::Lambda(..)
+            | Node::Range(..) => 0,
+            Node::Expr(_, op, _) => op.precedence(),
+            Node::Unary(UnaryOperator::Not, _) => 3,
+            Node::Quantity(..) => 9,
+            Node::Unary(..) => 10,
+            Node::Number(num) => match num {
+                Value::Complex(_) => 8,
+                Value::Rational(_) => 9,
+                num if *num < Value::default() => 10,
+                _ => 12,
+            },
+            Node::Function(..)
+            | Node::Const(_)
+            | Node::List(_)
+            | Node::Matrix(_)
+            | Node::Index(..) => 12,
+        }
+    }
+
+    fn fmt_child(&self, f: &mut std::fmt::Formatter<'_>, min_precedence: u8) -> std::fmt::Result {
+        if self.precedence() < min_precedence {
+            write!(f, "({})", self)
+        } else {
+            write!(f, "{}", self)
+        }
+    }
+}
+
+// comma separated, for arguments and list items
+pub fn fmt_list<T: Display>(f: &mut std::fmt::Formatter<'_>, items: &[T]) -> std::fmt::Result {
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", item)?;
+    }
+    Ok(())
+}
+
+impl Display for Node {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Node::Number(num) => write!(f, "{}", num),
+            Node::Quantity(num, unit) => write!(f, "{} {}", num, unit.name),
+            Node::Convert(node, unit) => {
+                node.fmt_child(f, 1)?;
+                write!(f, " to {}", unit.name)
+            }
+            Node::Expr(lhs, op, rhs) => {
+                let precedence = op.precedence();
+                let (lhs_min, rhs_min) = match op {
+                    // right associative so only the left side needs to bind tighter
+                    Operator::Pow => (precedence + 1, precedence),
+                    // `a + b + c` and `a * b * c` read the same either way
+                    Operator::Plus | Operator::Mult if matches!(rhs.as_ref(), Node::Expr(_, rhs_op, _) if rhs_op == op) => {
+                        (precedence, precedence)
+                    }
+                    _ => (precedence, precedence + 1),
+                };
+                lhs.fmt_child(f, lhs_min)?;
+                if *op == Operator::Pow {
+                    write!(f, "{}", op)?;
+                } else {
+                    write!(f, " {} ", op)?;
+                }
+                rhs.fmt_child(f, rhs_min)
+            }
+            Node::Unary(op, node) => {
+                write!(f, "{}", op)?;
+                node.fmt_child(f, self.precedence())
+            }
+            Node::Function(name, args) => {
+                write!(f, "{}(", name)?;
+                fmt_list(f, args)?;
+                write!(f, ")")
+            }
+            Node::List(items) => {
+                write!(f, "[")?;
+                fmt_list(f, items)?;
+                write!(f, "]")
+            }
+            Node::Matrix(rows) => {
+                write!(f, "[")?;
+                for (i, row) in rows.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "; ")?;
+                    }
+                    fmt_list(f, row)?;
+                }
+                write!(f, "]")
+            }
+            Node::Range(start, end) => {
+                start.fmt_child(f, 1)?;
+                write!(f, "..")?;
+                end.fmt_child(f, 1)
+            }
+            Node::Index(list, index) => {
+                list.fmt_child(f, 12)?;
+                write!(f, "[{}]", index)
+            }
+            Node::Const(name) => write!(f, "{}", name),
+            Node::AssignConst(name, node) => write!(f, "{} = {}", name, node),
+            Node::AssignFunc(name, vars, body) => {
+                write!(f, "{}({}) = {}", name, vars.join(", "), body)
+            }
+            Node::If(cond, then, otherwise) => {
+                write!(f, "if {} then {} else {}", cond, then, otherwise)
+            }
+            Node::Lambda(vars, body) => match vars.as_slice() {
+                [var] => write!(f, "{} -> {}", var, body),
+                vars => write!(f, "({}) -> {}", vars.join(", "), body),
+            },
+        }
+    }
+}
+
+impl Operator {
+    fn precedence(&self) -> u8 {
+        match self {
+            Operator::Or => 1,
+            Operator::And => 2,
+            Operator::Less
+            | Operator::LessEq
+            | Operator::Equal
+            | Operator::NotEqual
+            | Operator::Greater
+            | Operator::GreaterEq => 3,
+            Operator::BitOr => 4,
+            Operator::Xor => 5,
+            Operator::BitAnd => 6,
+            Operator::ShiftLeft | Operator::ShiftRight => 7,
+            Operator::Plus | Operator::Minus => 8,
+            Operator::Mult | Operator::Div => 9,
+            Operator::Pow => 11,
+        }
+    }
+}
+
+impl Display for Operator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let symbol = match self {
+            Operator::Plus => "+",
+            Operator::Minus => "-",
+            Operator::Mult => "*",
+            Operator::Div => "/",
+            Operator::Pow => "^",
+            Operator::Less => "<",
+            Operator::LessEq => "<=",
+            Operator::Equal => "==",
+            Operator::NotEqual => "!=",
+            Operator::Greater => ">",
+            Operator::GreaterEq => ">=",
+            Operator::And => "and",
+            Operator::Or => "or",
+            Operator::BitAnd => "&",
+            Operator::BitOr => "|",
+            Operator::Xor => "xor",
+            Operator::ShiftLeft => "<<",
+            Operator::ShiftRight => ">>",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+impl Display for UnaryOperator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UnaryOperator::Plus => write!(f, "+"),
+            UnaryOperator::Minus => write!(f, "-"),
+            UnaryOperator::Not => write!(f, "not "),
+            UnaryOperator::BitNot => write!(f, "~"),
+        }
+    }
 }
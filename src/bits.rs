@@ -0,0 +1,177 @@
+use std::fmt::Display;
+
+use num_bigint::BigInt;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+
+use crate::{
+    ast::{fmt_list, Operator},
+    value::Value,
+};
+
+// shifting further than this is refused rather than building huge numbers
+const MAX_SHIFT: u64 = 1 << 16;
+
+// a fixed size integer type like u8 or i32 that results wrap to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IntWidth {
+    bits: u32,
+    signed: bool,
+}
+
+impl IntWidth {
+    pub fn parse(name: &str) -> anyhow::Result<Self> {
+        let (signed, bits) = match name.split_at_checked(1) {
+            Some(("u", bits)) => (false, bits),
+            Some(("i", bits)) => (true, bits),
+            _ => anyhow::bail!("unknown integer width: {}", name),
+        };
+        match bits.parse() {
+            Ok(bits @ (8 | 16 | 32 | 64)) => Ok(Self { bits, signed }),
+            _ => anyhow::bail!("unknown integer width: {}", name),
+        }
+    }
+
+    // two's complement wrapping, 256 is 0 as a u8 and 128 is -128 as an i8
+    pub fn wrap(self, value: Value) -> Value {
+        match value {
+            Value::Int(int) => {
+                let modulus = BigInt::one() << self.bits;
+                let mut int = ((int % &modulus) + &modulus) % &modulus;
+                if self.signed && int >= &modulus >> 1 {
+                    int -= modulus;
+                }
+                Value::Int(int)
+            }
+            Value::Quantity(magnitude, unit) => {
+                Value::Quantity(Box::new(self.wrap(*magnitude)), unit)
+            }
+            Value::List(items) => {
+                Value::List(items.into_iter().map(|item| self.wrap(item)).collect())
+            }
+            Value::Matrix(matrix) => Value::Matrix(matrix.map(|item| self.wrap(item))),
+            value => value,
+        }
+    }
+}
+
+impl Display for IntWidth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", if self.signed { "i" } else { "u" }, self.bits)
+    }
+}
+
+fn int(op: &str, value: Value) -> anyhow::Result<BigInt> {
+    match value {
+        Value::Int(int) => Ok(int),
+        value => anyhow::bail!("{} expects integers but got {}", op, value),
+    }
+}
+
+fn shift(value: BigInt) -> anyhow::Result<usize> {
+    match value.to_u64() {
+        Some(shift) if shift <= MAX_SHIFT => Ok(shift as usize),
+        _ if value.is_negative() => anyhow::bail!("can not shift by a negative amount {}", value),
+        _ => anyhow::bail!("shift {} is too large", value),
+    }
+}
+
+// negative numbers act as if they had infinitely many leading ones, so -1 & x is x
+pub fn binary(op: Operator, lhs: Value, rhs: Value) -> anyhow::Result<Value> {
+    let symbol = op.to_string();
+    let (lhs, rhs) = (int(&symbol, lhs)?, int(&symbol, rhs)?);
+    Ok(Value::Int(match op {
+        Operator::BitAnd => lhs & rhs,
+        Operator::BitOr => lhs | rhs,
+        Operator::Xor => lhs ^ rhs,
+        Operator::ShiftLeft => lhs << shift(rhs)?,
+        // rounds towards negative infinity like an arithmetic shift
+        Operator::ShiftRight => lhs >> shift(rhs)?,
+        op => unreachable!("{} is not a bitwise operator", op),
+    }))
+}
+
+pub fn not(value: Value) -> anyhow::Result<Value> {
+    Ok(Value::Int(!int("~", value)?))
+}
+
+pub fn is_bitwise(op: &Operator) -> bool {
+    matches!(
+        op,
+        Operator::BitAnd
+            | Operator::BitOr
+            | Operator::Xor
+            | Operator::ShiftLeft
+            | Operator::ShiftRight
+    )
+}
+
+// integers with a 0x, 0b or 0o prefix, anything else is not a radix literal
+pub fn parse_literal(text: &str) -> Option<anyhow::Result<Value>> {
+    let radix = match text.get(..2)? {
+        "0x" => 16,
+        "0b" => 2,
+        "0o" => 8,
+        _ => return None,
+    };
+    let digits = &text[2..];
+    Some(match BigInt::parse_bytes(digits.as_bytes(), radix) {
+        Some(int) => Ok(Value::Int(int)),
+        None => Err(anyhow::anyhow!("invalid base {} number: {}", radix, text)),
+    })
+}
+
+pub fn prefix(radix: u32) -> anyhow::Result<&'static str> {
+    Ok(match radix {
+        2 => "0b",
+        8 => "0o",
+        10 => "",
+        16 => "0x",
+        radix => anyhow::bail!("the base must be 2, 8, 10 or 16 but got {}", radix),
+    })
+}
+
+// integers in another base keep their prefix so the output can be typed back in
+pub fn format_int(int: &BigInt, radix: u32) -> String {
+    let prefix = prefix(radix).unwrap_or_default();
+    let sign = if int.is_negative() { "-" } else { "" };
+    let digits = if int.is_zero() {
+        String::from("0")
+    } else {
+        int.abs().to_str_radix(radix)
+    };
+    format!("{}{}{}", sign, prefix, digits)
+}
+
+fn in_base(items: &[Value], radix: u32) -> Vec<InBase<'_>> {
+    items.iter().map(|item| InBase(item, radix)).collect()
+}
+
+// displays the integers inside a value in another base, other numbers are unchanged
+pub struct InBase<'a>(pub &'a Value, pub u32);
+
+impl Display for InBase<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Value::Int(int) => write!(f, "{}", format_int(int, self.1)),
+            Value::Quantity(magnitude, unit) => {
+                write!(f, "{} {}", InBase(magnitude, self.1), unit.name)
+            }
+            Value::List(items) => {
+                write!(f, "[")?;
+                fmt_list(f, &in_base(items, self.1))?;
+                write!(f, "]")
+            }
+            Value::Matrix(matrix) => {
+                write!(f, "[")?;
+                for (i, row) in matrix.rows().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "; ")?;
+                    }
+                    fmt_list(f, &in_base(row, self.1))?;
+                }
+                write!(f, "]")
+            }
+            value => write!(f, "{}", value),
+        }
+    }
+}
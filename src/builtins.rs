@@ -0,0 +1,925 @@
+use std::{cmp::Ordering, collections::HashMap, f64::consts, fmt::Display, rc::Rc};
+
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{ToPrimitive, Zero};
+
+use crate::{
+    ast::{Node, Operator},
+    interpreter::{binary, AngleMode, Interpreter},
+    matrix::Matrix,
+    scope::{Closure, Scope},
+    special, stats,
+    unit::Unit,
+    value::Value,
+};
+
+const MAX_BESSEL_ORDER: f64 = 10_000.;
+
+// how many arguments a built-in takes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Arity {
+    Exact(usize),
+    Range(usize, usize),
+    AtLeast(usize),
+}
+
+impl Arity {
+    pub fn accepts(self, len: usize) -> bool {
+        match self {
+            Arity::Exact(n) => len == n,
+            Arity::Range(lo, hi) => (lo..=hi).contains(&len),
+            Arity::AtLeast(n) => len >= n,
+        }
+    }
+}
+
+impl Display for Arity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Arity::Exact(1) => write!(f, "1 argument"),
+            Arity::Exact(n) => write!(f, "{} arguments", n),
+            Arity::Range(lo, hi) if lo + 1 == *hi => write!(f, "{} or {} arguments", lo, hi),
+            Arity::Range(lo, hi) => write!(f, "{} to {} arguments", lo, hi),
+            Arity::AtLeast(1) => write!(f, "at least 1 argument"),
+            Arity::AtLeast(n) => write!(f, "at least {} arguments", n),
+        }
+    }
+}
+
+// functions of one argument are applied to every element of a list,
+// except for Values and Nodes which see their arguments as given
+pub enum Eval {
+    // numbers that may have units
+    Quantity(fn(&[Value]) -> anyhow::Result<Value>),
+    // dimensionless numbers that may be complex
+    Number(fn(&[Value]) -> anyhow::Result<Value>),
+    // an angle, as a plain number in the angle mode or a quantity like 90 deg,
+    // with the exact values at multiples of a quarter turn where None is a pole
+    Trig(fn(&[Value]) -> anyhow::Result<Value>, [Option<i64>; 4]),
+    // dimensionless numbers giving an angle in the angle mode
+    InverseTrig(fn(&[Value]) -> anyhow::Result<Value>),
+    // dimensionless real numbers
+    Real(fn(&[f64]) -> anyhow::Result<f64>),
+    // any values, the implementation checks them
+    Values(fn(&mut Interpreter, &[Value]) -> anyhow::Result<Value>),
+    // the unevaluated arguments, for functions that bind a variable
+    Nodes(fn(&mut Interpreter, &[Node]) -> anyhow::Result<Value>),
+}
+
+pub struct Builtin {
+    pub name: &'static str,
+    pub usage: &'static str,
+    pub arity: Arity,
+    pub doc: &'static str,
+    pub eval: Eval,
+}
+
+pub fn find(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|builtin| builtin.name == name)
+}
+
+impl Builtin {
+    pub fn call(&self, inter: &mut Interpreter, args: Vec<Value>) -> anyhow::Result<Value> {
+        let elementwise = !matches!(self.eval, Eval::Values(_) | Eval::Nodes(_));
+        if let (true, Arity::Exact(1), [Value::List(items)]) =
+            (elementwise, self.arity, args.as_slice())
+        {
+            return items
+                .iter()
+                .map(|item| self.call(inter, vec![item.clone()]))
+                .collect::<anyhow::Result<_>>()
+                .map(Value::List);
+        }
+
+        if !self.arity.accepts(args.len()) {
+            anyhow::bail!(
+                "{} takes {} but {} were given",
+                self.name,
+                self.arity,
+                args.len()
+            );
+        }
+
+        match self.eval {
+            Eval::Quantity(eval) => {
+                if args.iter().any(|arg| !arg.is_number()) {
+                    anyhow::bail!("{} expects numbers", self.name);
+                }
+                eval(&args)
+            }
+            Eval::Number(eval) => {
+                if args.iter().any(|arg| !arg.is_number()) {
+                    anyhow::bail!("{} expects numbers", self.name);
+                }
+                if args.iter().any(Value::is_quantity) {
+                    anyhow::bail!("{} expects dimensionless arguments", self.name);
+                }
+                eval(&args)
+            }
+            Eval::Trig(eval, exact) => {
+                if !args[0].is_number() {
+                    anyhow::bail!("{} expects numbers", self.name);
+                }
+                trig(self.name, inter.angle_mode, &args[0], eval, exact)
+            }
+            Eval::InverseTrig(eval) => {
+                if args.iter().any(|arg| !arg.is_number()) {
+                    anyhow::bail!("{} expects numbers", self.name);
+                }
+                if args.iter().any(Value::is_quantity) {
+                    anyhow::bail!("{} expects dimensionless arguments", self.name);
+                }
+                inter.angle_mode.radians_in_mode(eval(&args)?)
+            }
+            Eval::Real(eval) => {
+                let args = args
+                    .iter()
+                    .map(|arg| real(self.name, arg))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                eval(&args).map(Value::from)
+            }
+            Eval::Values(eval) => eval(inter, &args),
+            Eval::Nodes(_) => unreachable!("{} is evaluated from its nodes", self.name),
+        }
+    }
+}
+
+// quantities like 90 deg are read by their unit whatever the mode is
+fn trig(
+    func: &str,
+    mode: AngleMode,
+    angle: &Value,
+    eval: fn(&[Value]) -> anyhow::Result<Value>,
+    exact: [Option<i64>; 4],
+) -> anyhow::Result<Value> {
+    if let Value::Quantity(magnitude, unit) = angle {
+        if let Some(mode) = AngleMode::from_unit(&unit.name) {
+            return trig(func, mode, magnitude, eval, exact);
+        }
+        let Ok(Value::Quantity(radians, _)) = angle.clone().convert(&Unit::parse("rad")?) else {
+            anyhow::bail!("{} expects an angle but got {}", func, angle);
+        };
+        return eval(&[*radians]);
+    }
+
+    if let (Some(quarter), Value::Int(int)) = (mode.quarter_turn(), angle) {
+        let quarter = BigInt::from(quarter);
+        if (int % &quarter).is_zero() {
+            let turns: BigInt = (int / quarter % 4 + 4) % 4;
+            return match exact[turns.to_usize().unwrap_or_default()] {
+                Some(value) => Ok(Value::from(value)),
+                None => anyhow::bail!("{} is undefined at {} {}", func, angle, mode.unit()),
+            };
+        }
+    }
+
+    eval(&[mode.to_radians(angle.clone())?])
+}
+
+fn real(func: &str, value: &Value) -> anyhow::Result<f64> {
+    if !value.is_number() || value.is_complex() || value.is_quantity() {
+        anyhow::bail!("{} expects real numbers but got {}", func, value);
+    }
+    Ok(value.to_f64())
+}
+
+// the special functions give infinity or NaN at their poles
+fn pole(func: &str, x: f64, res: f64) -> anyhow::Result<f64> {
+    if !res.is_finite() && x.is_finite() && (x <= 0. || x == 1.) {
+        anyhow::bail!("{} has a pole at {}", func, x);
+    }
+    Ok(res)
+}
+
+fn order(func: &str, n: f64) -> anyhow::Result<i64> {
+    if n.fract() != 0. || n.abs() > MAX_BESSEL_ORDER {
+        anyhow::bail!(
+            "{} expects an integer order up to {} but got {}",
+            func,
+            MAX_BESSEL_ORDER,
+            n
+        );
+    }
+    Ok(n as i64)
+}
+
+fn list<'a>(func: &str, args: &'a [Value]) -> anyhow::Result<&'a [Value]> {
+    match args {
+        [Value::List(items)] => Ok(items),
+        _ => anyhow::bail!("{} takes a list", func),
+    }
+}
+
+fn matrix<'a>(func: &str, args: &'a [Value]) -> anyhow::Result<&'a Matrix> {
+    match args {
+        [Value::Matrix(matrix)] => Ok(matrix),
+        _ => anyhow::bail!("{} takes a matrix", func),
+    }
+}
+
+fn function<'a>(func: &str, value: &'a Value) -> anyhow::Result<&'a Rc<Closure>> {
+    match value {
+        Value::Function(closure) => Ok(closure),
+        value => anyhow::bail!("{} expects a function but got {}", func, value),
+    }
+}
+
+// folds from the first item so units carry through, an empty list gives empty
+fn fold_list(items: &[Value], op: Operator, empty: i64) -> anyhow::Result<Value> {
+    let Some((first, rest)) = items.split_first() else {
+        return Ok(Value::from(empty));
+    };
+    rest.iter()
+        .try_fold(first.clone(), |acc, x| binary(op.clone(), acc, x.clone()))
+}
+
+// sum(list) and prod(list), or a series when there are more arguments
+fn series(inter: &mut Interpreter, func: &str, nodes: &[Node]) -> anyhow::Result<Value> {
+    let [node] = nodes else {
+        return inter.series(func, nodes);
+    };
+    let value = inter.step(node.clone())?;
+    match func {
+        "sum" => fold_list(list(func, &[value])?, Operator::Plus, 0),
+        _ => fold_list(list(func, &[value])?, Operator::Mult, 1),
+    }
+}
+
+fn extremum(func: &str, args: &[Value], keep: Ordering) -> anyhow::Result<Value> {
+    let args = match args {
+        [Value::List(items)] => items.as_slice(),
+        args => args,
+    };
+    let Some((first, rest)) = args.split_first() else {
+        anyhow::bail!("{} of an empty list", func);
+    };
+    if args.iter().any(|arg| !arg.is_number()) {
+        anyhow::bail!("{} expects numbers", func);
+    }
+    rest.iter()
+        .try_fold(first.clone(), |acc, x| match x.partial_cmp(&acc) {
+            Some(ordering) if ordering == keep => Ok(x.clone()),
+            Some(_) => Ok(acc),
+            None => Err(anyhow::anyhow!("can not compare {} and {}", acc, x)),
+        })
+}
+
+// real inside the domain, complex outside it
+fn complex_domain(
+    x: &Value,
+    in_domain: fn(f64) -> bool,
+    real: fn(f64) -> f64,
+    complex: fn(num_complex::Complex64) -> num_complex::Complex64,
+) -> anyhow::Result<Value> {
+    Ok(x.map_float(in_domain, real, complex))
+}
+
+fn everywhere(_: f64) -> bool {
+    true
+}
+
+fn positive(x: f64) -> bool {
+    x >= 0.
+}
+
+fn unit_interval(x: f64) -> bool {
+    x.abs() <= 1.
+}
+
+pub static BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "sqrt",
+        usage: "sqrt(x)",
+        arity: Arity::Exact(1),
+        doc: "the square root, exact for perfect squares",
+        eval: Eval::Quantity(|x| x[0].clone().sqrt()),
+    },
+    Builtin {
+        name: "cbrt",
+        usage: "cbrt(x)",
+        arity: Arity::Exact(1),
+        doc: "the real cube root, exact for perfect cubes",
+        eval: Eval::Quantity(|x| x[0].clone().cbrt()),
+    },
+    Builtin {
+        name: "abs",
+        usage: "abs(x)",
+        arity: Arity::Exact(1),
+        doc: "the absolute value, or magnitude of a complex number",
+        eval: Eval::Quantity(|x| Ok(x[0].clone().abs())),
+    },
+    Builtin {
+        name: "sign",
+        usage: "sign(x)",
+        arity: Arity::Exact(1),
+        doc: "-1, 0 or 1, or z / abs(z) for a complex number",
+        eval: Eval::Quantity(|x| Ok(x[0].clone().sign())),
+    },
+    Builtin {
+        name: "floor",
+        usage: "floor(x)",
+        arity: Arity::Exact(1),
+        doc: "the largest integer not above x",
+        eval: Eval::Quantity(|x| x[0].clone().round_with(BigRational::floor, f64::floor)),
+    },
+    Builtin {
+        name: "ceil",
+        usage: "ceil(x)",
+        arity: Arity::Exact(1),
+        doc: "the smallest integer not below x",
+        eval: Eval::Quantity(|x| x[0].clone().round_with(BigRational::ceil, f64::ceil)),
+    },
+    Builtin {
+        name: "round",
+        usage: "round(x)",
+        arity: Arity::Exact(1),
+        doc: "the nearest integer, halves round away from zero",
+        eval: Eval::Quantity(|x| x[0].clone().round_with(BigRational::round, f64::round)),
+    },
+    Builtin {
+        name: "trunc",
+        usage: "trunc(x)",
+        arity: Arity::Exact(1),
+        doc: "x with its fractional part removed",
+        eval: Eval::Quantity(|x| x[0].clone().round_with(BigRational::trunc, f64::trunc)),
+    },
+    Builtin {
+        name: "max",
+        usage: "max(a, b, ...) or max(list)",
+        arity: Arity::AtLeast(1),
+        doc: "the largest argument",
+        eval: Eval::Values(|_, args| extremum("max", args, Ordering::Greater)),
+    },
+    Builtin {
+        name: "min",
+        usage: "min(a, b, ...) or min(list)",
+        arity: Arity::AtLeast(1),
+        doc: "the smallest argument",
+        eval: Eval::Values(|_, args| extremum("min", args, Ordering::Less)),
+    },
+    Builtin {
+        name: "exp",
+        usage: "exp(x)",
+        arity: Arity::Exact(1),
+        doc: "e to the power of x",
+        eval: Eval::Number(|x| complex_domain(&x[0], everywhere, f64::exp, |z| z.exp())),
+    },
+    Builtin {
+        name: "ln",
+        usage: "ln(x)",
+        arity: Arity::Exact(1),
+        doc: "the natural logarithm",
+        eval: Eval::Number(|x| complex_domain(&x[0], positive, f64::ln, |z| z.ln())),
+    },
+    Builtin {
+        name: "log",
+        usage: "log(x) or log(x, base)",
+        arity: Arity::Range(1, 2),
+        doc: "the logarithm in base 10 or the given base",
+        eval: Eval::Number(|args| match args {
+            [x] => complex_domain(x, positive, f64::log10, |z| z.ln() / consts::LN_10),
+            [x, base] => Ok(
+                if x.is_complex() || base.is_complex() || x.to_f64() < 0. || base.to_f64() < 0. {
+                    Value::from_complex(x.to_complex().ln() / base.to_complex().ln())
+                } else {
+                    x.to_f64().log(base.to_f64()).into()
+                },
+            ),
+            _ => unreachable!("log takes 1 or 2 arguments"),
+        }),
+    },
+    Builtin {
+        name: "log2",
+        usage: "log2(x)",
+        arity: Arity::Exact(1),
+        doc: "the logarithm in base 2",
+        eval: Eval::Number(|x| {
+            complex_domain(&x[0], positive, f64::log2, |z| z.ln() / consts::LN_2)
+        }),
+    },
+    Builtin {
+        name: "sin",
+        usage: "sin(x)",
+        arity: Arity::Exact(1),
+        doc: "the sine",
+        eval: Eval::Trig(
+            |x| complex_domain(&x[0], everywhere, f64::sin, |z| z.sin()),
+            [Some(0), Some(1), Some(0), Some(-1)],
+        ),
+    },
+    Builtin {
+        name: "cos",
+        usage: "cos(x)",
+        arity: Arity::Exact(1),
+        doc: "the cosine",
+        eval: Eval::Trig(
+            |x| complex_domain(&x[0], everywhere, f64::cos, |z| z.cos()),
+            [Some(1), Some(0), Some(-1), Some(0)],
+        ),
+    },
+    Builtin {
+        name: "tan",
+        usage: "tan(x)",
+        arity: Arity::Exact(1),
+        doc: "the tangent",
+        eval: Eval::Trig(
+            |x| complex_domain(&x[0], everywhere, f64::tan, |z| z.tan()),
+            [Some(0), None, Some(0), None],
+        ),
+    },
+    Builtin {
+        name: "sec",
+        usage: "sec(x)",
+        arity: Arity::Exact(1),
+        doc: "the secant 1 / cos(x)",
+        eval: Eval::Trig(
+            |x| complex_domain(&x[0], everywhere, |x| 1. / x.cos(), |z| z.cos().inv()),
+            [Some(1), None, Some(-1), None],
+        ),
+    },
+    Builtin {
+        name: "csc",
+        usage: "csc(x)",
+        arity: Arity::Exact(1),
+        doc: "the cosecant 1 / sin(x)",
+        eval: Eval::Trig(
+            |x| complex_domain(&x[0], everywhere, |x| 1. / x.sin(), |z| z.sin().inv()),
+            [None, Some(1), None, Some(-1)],
+        ),
+    },
+    Builtin {
+        name: "cot",
+        usage: "cot(x)",
+        arity: Arity::Exact(1),
+        doc: "the cotangent 1 / tan(x)",
+        eval: Eval::Trig(
+            |x| complex_domain(&x[0], everywhere, |x| 1. / x.tan(), |z| z.tan().inv()),
+            [None, Some(0), None, Some(0)],
+        ),
+    },
+    Builtin {
+        name: "asin",
+        usage: "asin(x)",
+        arity: Arity::Exact(1),
+        doc: "the inverse sine, complex outside -1 to 1",
+        eval: Eval::InverseTrig(|x| complex_domain(&x[0], unit_interval, f64::asin, |z| z.asin())),
+    },
+    Builtin {
+        name: "acos",
+        usage: "acos(x)",
+        arity: Arity::Exact(1),
+        doc: "the inverse cosine, complex outside -1 to 1",
+        eval: Eval::InverseTrig(|x| complex_domain(&x[0], unit_interval, f64::acos, |z| z.acos())),
+    },
+    Builtin {
+        name: "atan",
+        usage: "atan(x)",
+        arity: Arity::Exact(1),
+        doc: "the inverse tangent",
+        eval: Eval::InverseTrig(|x| complex_domain(&x[0], everywhere, f64::atan, |z| z.atan())),
+    },
+    Builtin {
+        name: "atan2",
+        usage: "atan2(y, x)",
+        arity: Arity::Exact(2),
+        doc: "the angle of the point (x, y)",
+        eval: Eval::InverseTrig(|x| {
+            let [y, x] = [real("atan2", &x[0])?, real("atan2", &x[1])?];
+            Ok(Value::from(y.atan2(x)))
+        }),
+    },
+    Builtin {
+        name: "sinh",
+        usage: "sinh(x)",
+        arity: Arity::Exact(1),
+        doc: "the hyperbolic sine",
+        eval: Eval::Number(|x| complex_domain(&x[0], everywhere, f64::sinh, |z| z.sinh())),
+    },
+    Builtin {
+        name: "cosh",
+        usage: "cosh(x)",
+        arity: Arity::Exact(1),
+        doc: "the hyperbolic cosine",
+        eval: Eval::Number(|x| complex_domain(&x[0], everywhere, f64::cosh, |z| z.cosh())),
+    },
+    Builtin {
+        name: "tanh",
+        usage: "tanh(x)",
+        arity: Arity::Exact(1),
+        doc: "the hyperbolic tangent",
+        eval: Eval::Number(|x| complex_domain(&x[0], everywhere, f64::tanh, |z| z.tanh())),
+    },
+    Builtin {
+        name: "asinh",
+        usage: "asinh(x)",
+        arity: Arity::Exact(1),
+        doc: "the inverse hyperbolic sine",
+        eval: Eval::Number(|x| complex_domain(&x[0], everywhere, f64::asinh, |z| z.asinh())),
+    },
+    Builtin {
+        name: "acosh",
+        usage: "acosh(x)",
+        arity: Arity::Exact(1),
+        doc: "the inverse hyperbolic cosine, complex below 1",
+        eval: Eval::Number(|x| complex_domain(&x[0], |x| x >= 1., f64::acosh, |z| z.acosh())),
+    },
+    Builtin {
+        name: "atanh",
+        usage: "atanh(x)",
+        arity: Arity::Exact(1),
+        doc: "the inverse hyperbolic tangent, complex outside -1 to 1",
+        eval: Eval::Number(|x| complex_domain(&x[0], unit_interval, f64::atanh, |z| z.atanh())),
+    },
+    Builtin {
+        name: "re",
+        usage: "re(z)",
+        arity: Arity::Exact(1),
+        doc: "the real part",
+        eval: Eval::Number(|x| Ok(x[0].re())),
+    },
+    Builtin {
+        name: "im",
+        usage: "im(z)",
+        arity: Arity::Exact(1),
+        doc: "the imaginary part",
+        eval: Eval::Number(|x| Ok(x[0].im())),
+    },
+    Builtin {
+        name: "arg",
+        usage: "arg(z)",
+        arity: Arity::Exact(1),
+        doc: "the angle of a complex number",
+        eval: Eval::Number(|x| Ok(x[0].arg())),
+    },
+    Builtin {
+        name: "conj",
+        usage: "conj(z)",
+        arity: Arity::Exact(1),
+        doc: "the complex conjugate",
+        eval: Eval::Number(|x| Ok(x[0].conj())),
+    },
+    Builtin {
+        name: "gamma",
+        usage: "gamma(x)",
+        arity: Arity::Exact(1),
+        doc: "the gamma function, gamma(n) = (n - 1)!",
+        eval: Eval::Real(|x| pole("gamma", x[0], special::gamma(x[0]))),
+    },
+    Builtin {
+        name: "lgamma",
+        usage: "lgamma(x)",
+        arity: Arity::Exact(1),
+        doc: "ln(abs(gamma(x))), finite where gamma overflows",
+        eval: Eval::Real(|x| pole("lgamma", x[0], special::lgamma(x[0]))),
+    },
+    Builtin {
+        name: "beta",
+        usage: "beta(a, b)",
+        arity: Arity::Exact(2),
+        doc: "the beta function gamma(a) gamma(b) / gamma(a + b)",
+        eval: Eval::Real(|x| pole("beta", x[0].min(x[1]), special::beta(x[0], x[1]))),
+    },
+    Builtin {
+        name: "erf",
+        usage: "erf(x)",
+        arity: Arity::Exact(1),
+        doc: "the error function",
+        eval: Eval::Real(|x| Ok(special::erf(x[0]))),
+    },
+    Builtin {
+        name: "erfc",
+        usage: "erfc(x)",
+        arity: Arity::Exact(1),
+        doc: "the complementary error function 1 - erf(x)",
+        eval: Eval::Real(|x| Ok(special::erfc(x[0]))),
+    },
+    Builtin {
+        name: "zeta",
+        usage: "zeta(s)",
+        arity: Arity::Exact(1),
+        doc: "the Riemann zeta function",
+        eval: Eval::Real(|x| pole("zeta", x[0], special::zeta(x[0]))),
+    },
+    Builtin {
+        name: "besselj",
+        usage: "besselj(n, x)",
+        arity: Arity::Exact(2),
+        doc: "the Bessel function of the first kind of integer order n",
+        eval: Eval::Real(|x| Ok(special::bessel_j(order("besselj", x[0])?, x[1]))),
+    },
+    Builtin {
+        name: "bessely",
+        usage: "bessely(n, x)",
+        arity: Arity::Exact(2),
+        doc: "the Bessel function of the second kind of integer order n, for x > 0",
+        eval: Eval::Real(|x| {
+            if x[1] <= 0. {
+                anyhow::bail!("bessely is only defined for x > 0 but got {}", x[1]);
+            }
+            Ok(special::bessel_y(order("bessely", x[0])?, x[1]))
+        }),
+    },
+    Builtin {
+        name: "lambertw",
+        usage: "lambertw(x)",
+        arity: Arity::Exact(1),
+        doc: "the principal branch of the Lambert W function, w e^w = x",
+        eval: Eval::Real(|x| {
+            if x[0] < -1. / consts::E {
+                anyhow::bail!("lambertw is only defined for x >= -1/e but got {}", x[0]);
+            }
+            Ok(special::lambertw(x[0]))
+        }),
+    },
+    Builtin {
+        name: "solve",
+        usage: "solve(expr, x, guess), solve(expr, x, a, b) or solve(A, b)",
+        arity: Arity::Range(2, 4),
+        doc: "a root of expr near guess or between a and b, or the solution of A x = b",
+        eval: Eval::Nodes(|inter, nodes| inter.solve(nodes)),
+    },
+    Builtin {
+        name: "integrate",
+        usage: "integrate(expr, x, a, b)",
+        arity: Arity::Range(3, 4),
+        doc: "the integral of expr from a to b",
+        eval: Eval::Nodes(|inter, nodes| inter.integrate(nodes)),
+    },
+    Builtin {
+        name: "sum",
+        usage: "sum(expr, k, from, to) or sum(list)",
+        arity: Arity::Range(1, 4),
+        doc: "the sum of expr for k from from to to, or of a list",
+        eval: Eval::Nodes(|inter, nodes| series(inter, "sum", nodes)),
+    },
+    Builtin {
+        name: "prod",
+        usage: "prod(expr, k, from, to) or prod(list)",
+        arity: Arity::Range(1, 4),
+        doc: "the product of expr for k from from to to, or of a list",
+        eval: Eval::Nodes(|inter, nodes| series(inter, "prod", nodes)),
+    },
+    Builtin {
+        name: "diff",
+        usage: "diff(f)",
+        arity: Arity::Exact(1),
+        doc: "defines f' as the derivative of the function f",
+        eval: Eval::Nodes(|inter, nodes| match nodes {
+            [Node::Const(name)] => {
+                inter.derive(name)?;
+                Ok(Value::default())
+            }
+            _ => anyhow::bail!("diff takes the name of a function"),
+        }),
+    },
+    Builtin {
+        name: "apply",
+        usage: "apply(f, a, b, ...)",
+        arity: Arity::AtLeast(1),
+        doc: "f(a, b, ...)",
+        eval: Eval::Values(|inter, args| {
+            let f = function("apply", &args[0])?;
+            inter.call(f.name(), f, args[1..].to_vec())
+        }),
+    },
+    Builtin {
+        name: "fold",
+        usage: "fold(f, init, a, b, ...) or fold(f, init, list)",
+        arity: Arity::AtLeast(2),
+        doc: "f(...f(f(init, a), b)..., ...)",
+        eval: Eval::Values(|inter, args| {
+            let f = function("fold", &args[0])?;
+            let items = match &args[2..] {
+                [Value::List(items)] => items.as_slice(),
+                items => items,
+            };
+            items.iter().try_fold(args[1].clone(), |acc, x| {
+                inter.call(f.name(), f, vec![acc, x.clone()])
+            })
+        }),
+    },
+    Builtin {
+        name: "compose",
+        usage: "compose(f, g)",
+        arity: Arity::Exact(2),
+        doc: "the function f(g(x))",
+        eval: Eval::Values(|_, args| {
+            let (f, g) = (
+                function("compose", &args[0])?,
+                function("compose", &args[1])?,
+            );
+            // f and g live in the scope the composition closes over
+            let vars: Vec<String> = match g.vars.len() {
+                1 => vec![String::from("x")],
+                len => (1..=len).map(|i| format!("x{}", i)).collect(),
+            };
+            let inner = Node::Function(
+                String::from("g"),
+                vars.iter().cloned().map(Node::Const).collect(),
+            );
+            let body = Node::Function(String::from("f"), vec![inner]);
+            let scope = Scope::new(
+                HashMap::from([
+                    (String::from("f"), Value::Function(f.clone())),
+                    (String::from("g"), Value::Function(g.clone())),
+                ]),
+                None,
+            );
+            Ok(Value::Function(Rc::new(Closure::new(
+                vars,
+                body,
+                Some(Rc::new(scope)),
+            ))))
+        }),
+    },
+    Builtin {
+        name: "map",
+        usage: "map(f, list)",
+        arity: Arity::Exact(2),
+        doc: "the list of f applied to every element",
+        eval: Eval::Values(|inter, args| {
+            let f = function("map", &args[0])?;
+            list("map", &args[1..])?
+                .iter()
+                .map(|item| inter.call(f.name(), f, vec![item.clone()]))
+                .collect::<anyhow::Result<_>>()
+                .map(Value::List)
+        }),
+    },
+    Builtin {
+        name: "len",
+        usage: "len(list)",
+        arity: Arity::Exact(1),
+        doc: "the number of elements",
+        eval: Eval::Values(|_, args| Ok(Value::from(list("len", args)?.len() as i64))),
+    },
+    Builtin {
+        name: "mean",
+        usage: "mean(list)",
+        arity: Arity::Exact(1),
+        doc: "the arithmetic mean",
+        eval: Eval::Values(|_, args| {
+            let items = list("mean", args)?;
+            if items.is_empty() {
+                anyhow::bail!("mean of an empty list");
+            }
+            let sum = fold_list(items, Operator::Plus, 0)?;
+            binary(Operator::Div, sum, Value::from(items.len() as i64))
+        }),
+    },
+    Builtin {
+        name: "median",
+        usage: "median(list)",
+        arity: Arity::Exact(1),
+        doc: "the middle value, or the mean of the middle two",
+        eval: Eval::Values(|_, args| stats::median(list("median", args)?)),
+    },
+    Builtin {
+        name: "mode",
+        usage: "mode(list)",
+        arity: Arity::Exact(1),
+        doc: "the most common value, the smallest one on ties",
+        eval: Eval::Values(|_, args| stats::mode(list("mode", args)?)),
+    },
+    Builtin {
+        name: "var",
+        usage: "var(list)",
+        arity: Arity::Exact(1),
+        doc: "the sample variance",
+        eval: Eval::Values(|_, args| stats::variance("var", list("var", args)?, true)),
+    },
+    Builtin {
+        name: "stdev",
+        usage: "stdev(list)",
+        arity: Arity::Exact(1),
+        doc: "the sample standard deviation",
+        eval: Eval::Values(|_, args| stats::variance("stdev", list("stdev", args)?, true)?.sqrt()),
+    },
+    Builtin {
+        name: "pvar",
+        usage: "pvar(list)",
+        arity: Arity::Exact(1),
+        doc: "the population variance",
+        eval: Eval::Values(|_, args| stats::variance("pvar", list("pvar", args)?, false)),
+    },
+    Builtin {
+        name: "pstdev",
+        usage: "pstdev(list)",
+        arity: Arity::Exact(1),
+        doc: "the population standard deviation",
+        eval: Eval::Values(|_, args| {
+            stats::variance("pstdev", list("pstdev", args)?, false)?.sqrt()
+        }),
+    },
+    Builtin {
+        name: "quantile",
+        usage: "quantile(list, p)",
+        arity: Arity::Exact(2),
+        doc: "the value below which a fraction p of the list lies",
+        eval: Eval::Values(|_, args| stats::quantile(list("quantile", &args[..1])?, &args[1])),
+    },
+    Builtin {
+        name: "corr",
+        usage: "corr(xs, ys)",
+        arity: Arity::Exact(2),
+        doc: "the Pearson correlation coefficient",
+        eval: Eval::Values(|_, args| {
+            stats::corr(list("corr", &args[..1])?, list("corr", &args[1..])?)
+        }),
+    },
+    Builtin {
+        name: "normpdf",
+        usage: "normpdf(x) or normpdf(x, mu, sigma)",
+        arity: Arity::Range(1, 3),
+        doc: "the normal probability density",
+        eval: Eval::Real(stats::normpdf),
+    },
+    Builtin {
+        name: "normcdf",
+        usage: "normcdf(x) or normcdf(x, mu, sigma)",
+        arity: Arity::Range(1, 3),
+        doc: "the chance a normal variable is below x",
+        eval: Eval::Real(stats::normcdf),
+    },
+    Builtin {
+        name: "invnorm",
+        usage: "invnorm(p) or invnorm(p, mu, sigma)",
+        arity: Arity::Range(1, 3),
+        doc: "the x with normcdf(x) = p",
+        eval: Eval::Real(stats::invnorm),
+    },
+    Builtin {
+        name: "binompdf",
+        usage: "binompdf(n, p, k)",
+        arity: Arity::Exact(3),
+        doc: "the chance of k successes in n trials",
+        eval: Eval::Real(stats::binompdf),
+    },
+    Builtin {
+        name: "binomcdf",
+        usage: "binomcdf(n, p, k)",
+        arity: Arity::Exact(3),
+        doc: "the chance of at most k successes in n trials",
+        eval: Eval::Real(stats::binomcdf),
+    },
+    Builtin {
+        name: "poissonpdf",
+        usage: "poissonpdf(lambda, k)",
+        arity: Arity::Exact(2),
+        doc: "the chance of k events at rate lambda",
+        eval: Eval::Real(stats::poissonpdf),
+    },
+    Builtin {
+        name: "poissoncdf",
+        usage: "poissoncdf(lambda, k)",
+        arity: Arity::Exact(2),
+        doc: "the chance of at most k events at rate lambda",
+        eval: Eval::Real(stats::poissoncdf),
+    },
+    Builtin {
+        name: "tpdf",
+        usage: "tpdf(x, df)",
+        arity: Arity::Exact(2),
+        doc: "the Student t probability density",
+        eval: Eval::Real(stats::tpdf),
+    },
+    Builtin {
+        name: "tcdf",
+        usage: "tcdf(x, df)",
+        arity: Arity::Exact(2),
+        doc: "the chance a Student t variable is below x",
+        eval: Eval::Real(stats::tcdf),
+    },
+    Builtin {
+        name: "transpose",
+        usage: "transpose(A)",
+        arity: Arity::Exact(1),
+        doc: "the transposed matrix, a list becomes a column",
+        eval: Eval::Values(|_, args| match args {
+            [Value::List(items)] => Ok(Value::Matrix(Matrix::column(items.clone())?)),
+            args => Ok(Value::Matrix(matrix("transpose", args)?.transpose())),
+        }),
+    },
+    Builtin {
+        name: "det",
+        usage: "det(A)",
+        arity: Arity::Exact(1),
+        doc: "the determinant",
+        eval: Eval::Values(|_, args| matrix("det", args)?.det()),
+    },
+    Builtin {
+        name: "inv",
+        usage: "inv(A)",
+        arity: Arity::Exact(1),
+        doc: "the inverse matrix",
+        eval: Eval::Values(|_, args| matrix("inv", args)?.inv().map(Value::Matrix)),
+    },
+    Builtin {
+        name: "rank",
+        usage: "rank(A)",
+        arity: Arity::Exact(1),
+        doc: "the number of linearly independent rows",
+        eval: Eval::Values(|_, args| Ok(Value::from(matrix("rank", args)?.rank()? as i64))),
+    },
+];
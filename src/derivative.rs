@@ -0,0 +1,518 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    ast::{Node, Operator, UnaryOperator},
+    builtins::{self, Eval},
+    interpreter::Interpreter,
+    simplify::simplify,
+    value::Value,
+};
+
+fn num(value: i64) -> Node {
+    Node::Number(Value::from(value))
+}
+
+fn expr(lhs: Node, op: Operator, rhs: Node) -> Node {
+    Node::Expr(Box::new(lhs), op, Box::new(rhs))
+}
+
+fn neg(node: Node) -> Node {
+    Node::Unary(UnaryOperator::Minus, Box::new(node))
+}
+
+fn call(name: &str, args: Vec<Node>) -> Node {
+    Node::Function(name.to_owned(), args)
+}
+
+fn recip(node: Node) -> Node {
+    expr(num(1), Operator::Div, node)
+}
+
+fn one_minus_square(u: &Node) -> Node {
+    expr(
+        num(1),
+        Operator::Minus,
+        expr(u.clone(), Operator::Pow, num(2)),
+    )
+}
+
+// 2 / sqrt(pi) e^(-u^2), the derivative of erf
+fn gaussian(u: &Node) -> Node {
+    expr(
+        expr(
+            num(2),
+            Operator::Div,
+            call("sqrt", vec![Node::Const(String::from("pi"))]),
+        ),
+        Operator::Mult,
+        expr(
+            Node::Const(String::from("e")),
+            Operator::Pow,
+            neg(expr(u.clone(), Operator::Pow, num(2))),
+        ),
+    )
+}
+
+fn contains_var(node: &Node, var: &str) -> bool {
+    match node {
+        Node::Quantity(num, unit) => {
+            Node::quantity_as_product(num, unit, |name| name == var).is_some()
+        }
+        Node::Number(_) => false,
+        Node::Const(name) => name == var,
+        Node::Convert(node, _) | Node::Unary(_, node) => contains_var(node, var),
+        Node::Expr(lhs, _, rhs) => contains_var(lhs, var) || contains_var(rhs, var),
+        Node::Function(_, args) => args.iter().any(|arg| contains_var(arg, var)),
+        Node::If(cond, then, otherwise) => {
+            contains_var(cond, var) || contains_var(then, var) || contains_var(otherwise, var)
+        }
+        Node::Lambda(vars, body) => !vars.iter().any(|v| v == var) && contains_var(body, var),
+        Node::List(items) => items.iter().any(|item| contains_var(item, var)),
+        Node::Matrix(rows) => rows.iter().flatten().any(|item| contains_var(item, var)),
+        Node::Range(lhs, rhs) | Node::Index(lhs, rhs) => {
+            contains_var(lhs, var) || contains_var(rhs, var)
+        }
+        Node::AssignConst(..) | Node::AssignFunc(..) => false,
+    }
+}
+
+// names of every function called anywhere in node
+fn calls<'a>(node: &'a Node, names: &mut Vec<&'a str>) {
+    match node {
+        Node::Number(_) | Node::Quantity(..) | Node::Const(_) => {}
+        Node::Convert(node, _) | Node::Unary(_, node) | Node::Lambda(_, node) => calls(node, names),
+        Node::AssignConst(_, node) | Node::AssignFunc(_, _, node) => calls(node, names),
+        Node::Expr(lhs, _, rhs) | Node::Range(lhs, rhs) | Node::Index(lhs, rhs) => {
+            calls(lhs, names);
+            calls(rhs, names);
+        }
+        Node::Function(name, args) => {
+            names.push(name);
+            args.iter().for_each(|arg| calls(arg, names));
+        }
+        Node::If(cond, then, otherwise) => {
+            [cond, then, otherwise]
+                .into_iter()
+                .for_each(|node| calls(node, names));
+        }
+        Node::List(items) => items.iter().for_each(|item| calls(item, names)),
+        Node::Matrix(rows) => rows.iter().flatten().for_each(|item| calls(item, names)),
+    }
+}
+
+// replaces parameters with the given argument expressions
+fn substitute(node: &Node, args: &HashMap<&str, &Node>) -> Node {
+    match node {
+        Node::Const(name) => args
+            .get(name.as_str())
+            .map_or_else(|| node.clone(), |arg| (*arg).clone()),
+        Node::Convert(inner, unit) => {
+            Node::Convert(Box::new(substitute(inner, args)), unit.clone())
+        }
+        Node::Unary(op, inner) => Node::Unary(op.clone(), Box::new(substitute(inner, args))),
+        Node::Expr(lhs, op, rhs) => expr(substitute(lhs, args), op.clone(), substitute(rhs, args)),
+        Node::Function(name, fn_args) => Node::Function(
+            name.clone(),
+            fn_args.iter().map(|arg| substitute(arg, args)).collect(),
+        ),
+        Node::If(cond, then, otherwise) => Node::If(
+            Box::new(substitute(cond, args)),
+            Box::new(substitute(then, args)),
+            Box::new(substitute(otherwise, args)),
+        ),
+        Node::List(items) => Node::List(items.iter().map(|item| substitute(item, args)).collect()),
+        Node::Matrix(rows) => Node::Matrix(
+            rows.iter()
+                .map(|row| row.iter().map(|item| substitute(item, args)).collect())
+                .collect(),
+        ),
+        Node::Range(start, end) => Node::Range(
+            Box::new(substitute(start, args)),
+            Box::new(substitute(end, args)),
+        ),
+        Node::Index(list, index) => Node::Index(
+            Box::new(substitute(list, args)),
+            Box::new(substitute(index, args)),
+        ),
+        // the lambda's own parameters are not replaced
+        Node::Lambda(vars, body) => {
+            let mut args = args.clone();
+            args.retain(|name, _| !vars.iter().any(|var| var == name));
+            Node::Lambda(vars.clone(), Box::new(substitute(body, &args)))
+        }
+        _ => node.clone(),
+    }
+}
+
+impl Interpreter {
+    // registers the derivative of a one variable user function as `func'` and returns its name
+    pub fn derive(&mut self, func: &str) -> anyhow::Result<String> {
+        let name = format!("{}'", func);
+        if self.funcs.contains_key(&name) {
+            return Ok(name);
+        }
+
+        if !self.funcs.contains_key(func) {
+            if let Some(base) = func.strip_suffix('\'') {
+                self.derive(base)?;
+            }
+        }
+
+        let Some((vars, body)) = self.funcs.get(func).cloned() else {
+            anyhow::bail!("invalid function name: {}", func);
+        };
+        let [var] = vars.as_slice() else {
+            anyhow::bail!(
+                "can only differentiate functions of one variable but {} has {}",
+                func,
+                vars.len()
+            );
+        };
+
+        // a recursive function refers to its own derivative, so the name is reserved up front
+        self.funcs.insert(name.clone(), (vars.clone(), num(0)));
+        match self.derivative(&body, var) {
+            Ok(body) => {
+                self.funcs.insert(name.clone(), (vars, simplify(body)));
+                Ok(name)
+            }
+            Err(err) => {
+                self.funcs.remove(&name);
+                Err(err)
+            }
+        }
+    }
+
+    // whether func calls itself, directly or through other user functions
+    fn is_recursive(&self, func: &str) -> bool {
+        let mut seen = HashSet::new();
+        let mut pending = vec![func];
+        while let Some(name) = pending.pop() {
+            let Some((_, body)) = self.funcs.get(name) else {
+                continue;
+            };
+            let mut called = Vec::new();
+            calls(body, &mut called);
+            for name in called {
+                if name == func {
+                    return true;
+                }
+                if seen.insert(name) {
+                    pending.push(name);
+                }
+            }
+        }
+        false
+    }
+
+    // symbolic derivative of node with respect to var
+    pub fn derivative(&mut self, node: &Node, var: &str) -> anyhow::Result<Node> {
+        Ok(match node {
+            // `3t` differentiated with respect to t is a product rather than a quantity
+            Node::Quantity(value, unit) => {
+                match Node::quantity_as_product(value, unit, |name| name == var) {
+                    Some(product) => self.derivative(&product, var)?,
+                    None => num(0),
+                }
+            }
+            Node::Number(_) => num(0),
+            Node::Const(name) => num((name == var) as i64),
+            Node::Unary(UnaryOperator::Not, _) => anyhow::bail!("can not differentiate not"),
+            Node::Unary(UnaryOperator::BitNot, _) => anyhow::bail!("can not differentiate ~"),
+            Node::Unary(op, inner) => {
+                Node::Unary(op.clone(), Box::new(self.derivative(inner, var)?))
+            }
+            Node::Expr(lhs, op, rhs) => {
+                let (u, v) = (lhs.as_ref().clone(), rhs.as_ref().clone());
+                let du = self.derivative(&u, var)?;
+                let dv = self.derivative(&v, var)?;
+                match op {
+                    Operator::Plus | Operator::Minus => expr(du, op.clone(), dv),
+                    // u'v + uv'
+                    Operator::Mult => expr(
+                        expr(du, Operator::Mult, v),
+                        Operator::Plus,
+                        expr(u, Operator::Mult, dv),
+                    ),
+                    // (u'v - uv') / v^2
+                    Operator::Div => expr(
+                        expr(
+                            expr(du, Operator::Mult, v.clone()),
+                            Operator::Minus,
+                            expr(u, Operator::Mult, dv),
+                        ),
+                        Operator::Div,
+                        expr(v, Operator::Pow, num(2)),
+                    ),
+                    Operator::Pow if !contains_var(&v, var) => expr(
+                        expr(
+                            v.clone(),
+                            Operator::Mult,
+                            expr(u, Operator::Pow, expr(v, Operator::Minus, num(1))),
+                        ),
+                        Operator::Mult,
+                        du,
+                    ),
+                    Operator::Pow if !contains_var(&u, var) => expr(
+                        expr(node.clone(), Operator::Mult, call("ln", vec![u])),
+                        Operator::Mult,
+                        dv,
+                    ),
+                    // u^v * (v' ln(u) + v u' / u)
+                    Operator::Pow => expr(
+                        node.clone(),
+                        Operator::Mult,
+                        expr(
+                            expr(dv, Operator::Mult, call("ln", vec![u.clone()])),
+                            Operator::Plus,
+                            expr(expr(v, Operator::Mult, du), Operator::Div, u),
+                        ),
+                    ),
+                    op => anyhow::bail!("can not differentiate {}", op),
+                }
+            }
+            Node::Function(name, args) => self.function_derivative(name, args, var)?,
+            // piecewise functions are differentiated branch by branch
+            Node::If(cond, then, otherwise) => Node::If(
+                cond.clone(),
+                Box::new(self.derivative(then, var)?),
+                Box::new(self.derivative(otherwise, var)?),
+            ),
+            Node::Convert(..) => anyhow::bail!("can not differentiate unit conversions"),
+            Node::Lambda(..) => anyhow::bail!("can not differentiate functions"),
+            // lists are differentiated element by element
+            Node::List(items) => Node::List(
+                items
+                    .iter()
+                    .map(|item| self.derivative(item, var))
+                    .collect::<anyhow::Result<_>>()?,
+            ),
+            Node::Matrix(rows) => Node::Matrix(
+                rows.iter()
+                    .map(|row| row.iter().map(|item| self.derivative(item, var)).collect())
+                    .collect::<anyhow::Result<_>>()?,
+            ),
+            Node::Range(..) => anyhow::bail!("can not differentiate ranges"),
+            Node::Index(list, index) if !contains_var(index, var) => {
+                Node::Index(Box::new(self.derivative(list, var)?), index.clone())
+            }
+            Node::Index(..) => anyhow::bail!("can not differentiate with respect to an index"),
+            Node::AssignConst(..) | Node::AssignFunc(..) => {
+                anyhow::bail!("can not differentiate assignments")
+            }
+        })
+    }
+
+    // trig functions read their argument in the angle mode and the inverses return one
+    fn angle_scale(&self, name: &str, node: Node) -> Node {
+        let Some(half) = self.angle_mode.half_turn() else {
+            return node;
+        };
+        let radians = expr(Node::Const(String::from("pi")), Operator::Div, num(half));
+        match builtins::find(name).map(|builtin| &builtin.eval) {
+            Some(Eval::Trig(..)) => expr(node, Operator::Mult, radians),
+            Some(Eval::InverseTrig(_)) => expr(node, Operator::Div, radians),
+            _ => node,
+        }
+    }
+
+    fn function_derivative(
+        &mut self,
+        name: &str,
+        args: &[Node],
+        var: &str,
+    ) -> anyhow::Result<Node> {
+        if let [u] = args {
+            // f'(u) without the inner derivative u'
+            let outer = match name {
+                "sqrt" => Some(expr(
+                    num(1),
+                    Operator::Div,
+                    expr(num(2), Operator::Mult, call(name, vec![u.clone()])),
+                )),
+                "ln" => Some(expr(num(1), Operator::Div, u.clone())),
+                "log" => Some(expr(
+                    num(1),
+                    Operator::Div,
+                    expr(u.clone(), Operator::Mult, call("ln", vec![num(10)])),
+                )),
+                "abs" => Some(expr(u.clone(), Operator::Div, call(name, vec![u.clone()]))),
+                "sin" => Some(call("cos", vec![u.clone()])),
+                "cos" => Some(neg(call("sin", vec![u.clone()]))),
+                "tan" => Some(expr(
+                    num(1),
+                    Operator::Div,
+                    expr(call("cos", vec![u.clone()]), Operator::Pow, num(2)),
+                )),
+                "cbrt" => Some(recip(expr(
+                    num(3),
+                    Operator::Mult,
+                    expr(call(name, vec![u.clone()]), Operator::Pow, num(2)),
+                ))),
+                "exp" => Some(call(name, vec![u.clone()])),
+                "log2" => Some(recip(expr(
+                    u.clone(),
+                    Operator::Mult,
+                    call("ln", vec![num(2)]),
+                ))),
+                "sec" => Some(expr(
+                    call(name, vec![u.clone()]),
+                    Operator::Mult,
+                    call("tan", vec![u.clone()]),
+                )),
+                "csc" => Some(neg(expr(
+                    call(name, vec![u.clone()]),
+                    Operator::Mult,
+                    call("cot", vec![u.clone()]),
+                ))),
+                "cot" => Some(neg(expr(
+                    call("csc", vec![u.clone()]),
+                    Operator::Pow,
+                    num(2),
+                ))),
+                // 1 / sqrt(1 - u^2)
+                "asin" => Some(recip(call("sqrt", vec![one_minus_square(u)]))),
+                "acos" => Some(neg(recip(call("sqrt", vec![one_minus_square(u)])))),
+                "atan" => Some(recip(expr(
+                    num(1),
+                    Operator::Plus,
+                    expr(u.clone(), Operator::Pow, num(2)),
+                ))),
+                "sinh" => Some(call("cosh", vec![u.clone()])),
+                "cosh" => Some(call("sinh", vec![u.clone()])),
+                "tanh" => Some(recip(expr(
+                    call("cosh", vec![u.clone()]),
+                    Operator::Pow,
+                    num(2),
+                ))),
+                "asinh" => Some(recip(call(
+                    "sqrt",
+                    vec![expr(
+                        expr(u.clone(), Operator::Pow, num(2)),
+                        Operator::Plus,
+                        num(1),
+                    )],
+                ))),
+                "acosh" => Some(recip(call(
+                    "sqrt",
+                    vec![expr(
+                        expr(u.clone(), Operator::Pow, num(2)),
+                        Operator::Minus,
+                        num(1),
+                    )],
+                ))),
+                "atanh" => Some(recip(one_minus_square(u))),
+                "erf" => Some(gaussian(u)),
+                "erfc" => Some(neg(gaussian(u))),
+                // e^-W(u) / (1 + W(u)), which unlike W / (u (1 + W)) is defined at 0
+                "lambertw" => Some(expr(
+                    expr(
+                        Node::Const(String::from("e")),
+                        Operator::Pow,
+                        neg(call(name, vec![u.clone()])),
+                    ),
+                    Operator::Div,
+                    expr(num(1), Operator::Plus, call(name, vec![u.clone()])),
+                )),
+                _ => None,
+            };
+            if let Some(outer) = outer {
+                let outer = self.angle_scale(name, outer);
+                return Ok(expr(outer, Operator::Mult, self.derivative(u, var)?));
+            }
+        }
+
+        match (name, args) {
+            // log(u, b) = ln(u) / ln(b)
+            ("log", [u, base]) => {
+                let rewritten = expr(
+                    call("ln", vec![u.clone()]),
+                    Operator::Div,
+                    call("ln", vec![base.clone()]),
+                );
+                self.derivative(&rewritten, var)
+            }
+            // (x y' - y x') / (x^2 + y^2)
+            ("atan2", [y, x]) => {
+                let dy = self.derivative(y, var)?;
+                let dx = self.derivative(x, var)?;
+                Ok(self.angle_scale(
+                    name,
+                    expr(
+                        expr(
+                            expr(x.clone(), Operator::Mult, dy),
+                            Operator::Minus,
+                            expr(y.clone(), Operator::Mult, dx),
+                        ),
+                        Operator::Div,
+                        expr(
+                            expr(x.clone(), Operator::Pow, num(2)),
+                            Operator::Plus,
+                            expr(y.clone(), Operator::Pow, num(2)),
+                        ),
+                    ),
+                ))
+            }
+            // (f(n - 1, u) - f(n + 1, u)) / 2 for both kinds of Bessel function
+            ("besselj" | "bessely", [n, u]) if !contains_var(n, var) => {
+                let order = |op| expr(n.clone(), op, num(1));
+                Ok(expr(
+                    expr(
+                        expr(
+                            call(name, vec![order(Operator::Minus), u.clone()]),
+                            Operator::Minus,
+                            call(name, vec![order(Operator::Plus), u.clone()]),
+                        ),
+                        Operator::Div,
+                        num(2),
+                    ),
+                    Operator::Mult,
+                    self.derivative(u, var)?,
+                ))
+            }
+            _ if builtins::find(name).is_some_and(|builtin| !builtin.arity.accepts(args.len())) => {
+                anyhow::bail!("wrong number of arguments to {}", name)
+            }
+            // built-ins without a rule above, like floor or gamma
+            _ if builtins::find(name).is_some() => {
+                anyhow::bail!("can not differentiate {}", name)
+            }
+            // a constant followed by parentheses is a product
+            (_, [u]) if self.lookup_function(name).is_none() && !name.ends_with('\'') => {
+                let product = expr(Node::Const(name.to_owned()), Operator::Mult, u.clone());
+                self.derivative(&product, var)
+            }
+            (_, [u]) => {
+                let derived = self.derive(name)?;
+                Ok(expr(
+                    call(&derived, vec![u.clone()]),
+                    Operator::Mult,
+                    self.derivative(u, var)?,
+                ))
+            }
+            _ => {
+                // functions of several variables are inlined and differentiated directly
+                let Some((vars, body)) = self.funcs.get(name).cloned() else {
+                    anyhow::bail!("invalid function name: {}", name);
+                };
+                if vars.len() != args.len() {
+                    anyhow::bail!(
+                        "{} takes {} arguments but {} were given",
+                        name,
+                        vars.len(),
+                        args.len()
+                    );
+                }
+                // inlining would never end since how deep it goes depends on the arguments
+                if self.is_recursive(name) {
+                    anyhow::bail!(
+                        "can not differentiate {} since it calls itself with several arguments",
+                        name
+                    );
+                }
+                let bindings = vars.iter().map(String::as_str).zip(args).collect();
+                self.derivative(&substitute(&body, &bindings), var)
+            }
+        }
+    }
+}
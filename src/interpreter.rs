@@ -1,57 +1,466 @@
-use std::{collections::HashMap, f64::consts, path::PathBuf, fs};
+use std::{
+    collections::{BTreeSet, HashMap},
+    f64::consts,
+    fs,
+    path::PathBuf,
+    rc::Rc,
+};
 
-use crate::{ast::Node, parser::Parser};
+use num_bigint::BigInt;
+use num_complex::Complex64;
+use num_traits::{Signed, ToPrimitive};
 
-#[derive(Default, Clone)]
+use crate::{
+    ast::{Node, Operator},
+    bits::{self, IntWidth},
+    builtins::{self, Arity, Builtin, Eval},
+    matrix::Matrix,
+    parser::Parser,
+    scope::{Closure, Scope},
+    value::Value,
+};
+
+// functions are values but can not be used as numbers
+fn number(value: Value) -> anyhow::Result<Value> {
+    if value.is_function() {
+        anyhow::bail!("expected a number but got the function {}", value);
+    }
+    Ok(value)
+}
+
+// integer results wrap when a width like u8 is set
+fn wrap(width: Option<IntWidth>, value: Value) -> Value {
+    match width {
+        Some(width) => width.wrap(value),
+        None => value,
+    }
+}
+
+pub fn binary(op: Operator, lhs: Value, rhs: Value) -> anyhow::Result<Value> {
+    // arithmetic on lists works element by element, a single value is used with every element
+    let arithmetic = matches!(
+        op,
+        Operator::Plus | Operator::Minus | Operator::Mult | Operator::Div | Operator::Pow
+    );
+    let elementwise = arithmetic || bits::is_bitwise(&op);
+    match (lhs, rhs) {
+        (lhs, rhs) if arithmetic && (lhs.is_matrix() || rhs.is_matrix()) => {
+            matrix_binary(op, lhs, rhs)
+        }
+        (Value::List(lhs), Value::List(rhs)) if elementwise => {
+            if lhs.len() != rhs.len() {
+                anyhow::bail!(
+                    "lists have different lengths: {} and {}",
+                    lhs.len(),
+                    rhs.len()
+                );
+            }
+            lhs.into_iter()
+                .zip(rhs)
+                .map(|(lhs, rhs)| binary(op.clone(), lhs, rhs))
+                .collect::<anyhow::Result<_>>()
+                .map(Value::List)
+        }
+        (Value::List(lhs), rhs) if elementwise => lhs
+            .into_iter()
+            .map(|lhs| binary(op.clone(), lhs, rhs.clone()))
+            .collect::<anyhow::Result<_>>()
+            .map(Value::List),
+        (lhs, Value::List(rhs)) if elementwise => rhs
+            .into_iter()
+            .map(|rhs| binary(op.clone(), lhs.clone(), rhs))
+            .collect::<anyhow::Result<_>>()
+            .map(Value::List),
+        (lhs, rhs) => numbers(op, number(lhs)?, number(rhs)?),
+    }
+}
+
+// `*` between matrices and vectors is matrix multiplication, other operators work element by element
+fn matrix_binary(op: Operator, lhs: Value, rhs: Value) -> anyhow::Result<Value> {
+    match (lhs, rhs) {
+        (Value::Matrix(lhs), Value::Matrix(rhs)) => match op {
+            Operator::Mult => lhs.mul(&rhs).map(Value::Matrix),
+            Operator::Plus | Operator::Minus => lhs
+                .zip_with(rhs, |a, b| numbers(op.clone(), a, b))
+                .map(Value::Matrix),
+            op => anyhow::bail!("can not use {} between matrices", op),
+        },
+        // lists are used as column vectors on the right and row vectors on the left
+        (Value::Matrix(lhs), Value::List(rhs)) if op == Operator::Mult => {
+            Ok(Value::List(lhs.mul(&Matrix::column(rhs)?)?.into_items()))
+        }
+        (Value::List(lhs), Value::Matrix(rhs)) if op == Operator::Mult => {
+            Ok(Value::List(Matrix::row(lhs)?.mul(&rhs)?.into_items()))
+        }
+        (Value::Matrix(matrix), Value::Int(exp)) if op == Operator::Pow => {
+            let Some(exp) = exp.to_i64() else {
+                anyhow::bail!("matrix power {} is too large", exp);
+            };
+            matrix.pow(exp).map(Value::Matrix)
+        }
+        (Value::Matrix(_), exp) if op == Operator::Pow => {
+            anyhow::bail!(
+                "matrices can only be raised to integer powers but got {}",
+                exp
+            )
+        }
+        (Value::Matrix(lhs), rhs) if rhs.is_number() => lhs
+            .try_map(|item| numbers(op.clone(), item, rhs.clone()))
+            .map(Value::Matrix),
+        (lhs, Value::Matrix(rhs))
+            if lhs.is_number() && op != Operator::Div && op != Operator::Pow =>
+        {
+            rhs.try_map(|item| numbers(op.clone(), lhs.clone(), item))
+                .map(Value::Matrix)
+        }
+        (lhs, rhs) => anyhow::bail!("can not use {} between {} and {}", op, lhs, rhs),
+    }
+}
+
+fn numbers(op: Operator, lhs: Value, rhs: Value) -> anyhow::Result<Value> {
+    match op {
+        Operator::Plus => lhs.checked_add(rhs),
+        Operator::Minus => lhs.checked_sub(rhs),
+        Operator::Mult => Ok(lhs * rhs),
+        Operator::Div => lhs.checked_div(rhs),
+        Operator::Pow => lhs.pow(rhs),
+        op if bits::is_bitwise(&op) => bits::binary(op, lhs, rhs),
+        op => compare(op, lhs, rhs),
+    }
+}
+
+// ranges are inclusive, `1..3` is [1, 2, 3]
+fn range(start: Value, end: Value) -> anyhow::Result<Value> {
+    let (Value::Int(start), Value::Int(end)) = (&start, &end) else {
+        anyhow::bail!(
+            "range bounds must be integers but got {} and {}",
+            start,
+            end
+        );
+    };
+    if (end - start)
+        .to_u64()
+        .is_some_and(|len| len >= MAX_LIST_LEN)
+    {
+        anyhow::bail!("range {}..{} is too long", start, end);
+    }
+
+    let mut items = Vec::new();
+    let mut i = start.clone();
+    while &i <= end {
+        items.push(Value::Int(i.clone()));
+        i += 1;
+    }
+    Ok(Value::List(items))
+}
+
+// indices start at 0 and negative indices count from the end, a list of indices picks several
+fn element(items: &[Value], index: Value) -> anyhow::Result<Value> {
+    let i = match index {
+        Value::List(indices) => {
+            return indices
+                .into_iter()
+                .map(|index| element(items, index))
+                .collect::<anyhow::Result<_>>()
+                .map(Value::List)
+        }
+        Value::Int(i) => i,
+        index => anyhow::bail!("list indices must be integers but got {}", index),
+    };
+
+    let len = BigInt::from(items.len());
+    let position = if i.is_negative() {
+        &len + &i
+    } else {
+        i.clone()
+    };
+    match position.to_usize().and_then(|position| items.get(position)) {
+        Some(item) => Ok(item.clone()),
+        None => anyhow::bail!("index {} is out of range for a list of length {}", i, len),
+    }
+}
+
+// a built-in used by name like `map(sqrt, xs)` is a function of its arguments
+fn builtin_closure(name: &str) -> Option<Closure> {
+    let builtin = builtins::find(name)?;
+    let vars: Vec<String> = match (&builtin.eval, builtin.arity) {
+        (Eval::Nodes(_), _) => return None,
+        (_, Arity::Exact(1)) => vec![String::from("x")],
+        (_, Arity::Exact(len)) => (1..=len).map(|i| format!("x{}", i)).collect(),
+        _ => return None,
+    };
+    let body = Node::Function(
+        name.to_owned(),
+        vars.iter().cloned().map(Node::Const).collect(),
+    );
+    Some(Closure::new(vars, body, None).named(name))
+}
+
+fn compare(op: Operator, lhs: Value, rhs: Value) -> anyhow::Result<Value> {
+    let res = match op {
+        Operator::Equal => lhs == rhs,
+        Operator::NotEqual => lhs != rhs,
+        _ => {
+            let Some(ordering) = lhs.partial_cmp(&rhs) else {
+                anyhow::bail!("can not compare {} and {}", lhs, rhs);
+            };
+            match op {
+                Operator::Less => ordering.is_lt(),
+                Operator::LessEq => ordering.is_le(),
+                Operator::Greater => ordering.is_gt(),
+                Operator::GreaterEq => ordering.is_ge(),
+                _ => unreachable!("{} is not a comparison", op),
+            }
+        }
+    };
+    Ok(Value::from_bool(res))
+}
+
+// ranges longer than this are refused
+const MAX_LIST_LEN: u64 = 10_000_000;
+// deeper calls are almost always runaway recursion, and would overflow the stack
+pub const DEFAULT_RECURSION_LIMIT: usize = 1000;
+// `!limit` can go up to this, the calculator thread gets STACK_PER_CALL for each call
+pub const MAX_RECURSION_LIMIT: usize = 2000;
+// stack reserved per call, a debug build uses about 115 KiB for a function whose body nests
+// a dozen operators around the recursive call and release builds far less, so this leaves
+// room for deeper bodies
+pub const STACK_PER_CALL: usize = 256 * 1024;
+// how many calls a recursion error lists
+const STACK_TRACE_FRAMES: usize = 8;
+
+// how plain numbers given to trig functions and returned by their inverses are read
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum AngleMode {
+    #[default]
+    Radians,
+    Degrees,
+    Gradians,
+}
+
+impl AngleMode {
+    // the `!rad`, `!deg` and `!grad` commands, also used as directives in files
+    pub fn from_command(text: &str) -> Option<Self> {
+        let command = text.split('#').next().unwrap_or_default();
+        Self::from_unit(command.trim().strip_prefix('!')?)
+    }
+
+    pub fn from_unit(name: &str) -> Option<Self> {
+        [AngleMode::Radians, AngleMode::Degrees, AngleMode::Gradians]
+            .into_iter()
+            .find(|mode| mode.unit() == name)
+    }
+
+    pub fn unit(self) -> &'static str {
+        match self {
+            AngleMode::Radians => "rad",
+            AngleMode::Degrees => "deg",
+            AngleMode::Gradians => "grad",
+        }
+    }
+
+    // a half turn in the mode's unit, None for radians
+    pub fn half_turn(self) -> Option<i64> {
+        self.quarter_turn().map(|quarter| 2 * quarter)
+    }
+
+    // multiplying before dividing by pi keeps asin(1) at exactly 90 degrees
+    pub fn to_radians(self, angle: Value) -> anyhow::Result<Value> {
+        match self.half_turn() {
+            None => Ok(angle),
+            Some(half) => (angle * Value::Float(consts::PI)).checked_div(Value::from(half)),
+        }
+    }
+
+    pub fn radians_in_mode(self, angle: Value) -> anyhow::Result<Value> {
+        match self.half_turn() {
+            None => Ok(angle),
+            Some(half) => (angle * Value::from(half)).checked_div(Value::Float(consts::PI)),
+        }
+    }
+
+    // a quarter turn, when it is a whole number of units
+    pub fn quarter_turn(self) -> Option<i64> {
+        match self {
+            AngleMode::Radians => None,
+            AngleMode::Degrees => Some(90),
+            AngleMode::Gradians => Some(100),
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct Interpreter {
-    pub consts: HashMap<String, f64>,
-    pub funcs: HashMap<String, (String, Node)>,
-    pub ans: f64,
-    pub executed_lines: Vec<String>
+    // globals, calls never change these
+    pub consts: HashMap<String, Value>,
+    // parameters of the calls being evaluated, None at the top level
+    pub scope: Option<Rc<Scope>>,
+    pub funcs: HashMap<String, (Vec<String>, Node)>,
+    pub ans: Value,
+    pub executed_lines: Vec<String>,
+    pub recursion_limit: usize,
+    // names of the user functions currently being evaluated, innermost last
+    pub call_stack: Vec<String>,
+    pub angle_mode: AngleMode,
+    // integer results wrap to this type when it is set
+    pub int_width: Option<IntWidth>,
+    // integers are printed in this base
+    pub output_base: u32,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self {
+            consts: HashMap::new(),
+            scope: None,
+            funcs: HashMap::new(),
+            ans: Value::default(),
+            executed_lines: Vec::new(),
+            recursion_limit: DEFAULT_RECURSION_LIMIT,
+            call_stack: Vec::new(),
+            angle_mode: AngleMode::default(),
+            int_width: None,
+            output_base: 10,
+        }
+    }
 }
 
 impl Interpreter {
-    fn functions(&mut self, func: String, x: f64) -> anyhow::Result<f64> {
-        Ok(match func.as_str() {
-            "sqrt" => x.sqrt(),
-            "ln" => x.ln(),
-            "abs" => x.abs(),
-            "cos" => x.cos(),
-            "sin" => x.sin(),
-            "tan" => x.tan(),
-            "log" => x.log10(),
-            _ => {
-                if let Some((var, body)) = self
-                    .funcs
-                    .get(&func)
-                    .map(|(var, body)| (var.clone(), body.clone()))
-                {
-                    let temp = self.consts.insert(var.clone(), x);
+    fn functions(&mut self, func: String, args: Vec<Value>) -> anyhow::Result<Value> {
+        if let Some(builtin) = builtins::find(&func) {
+            return builtin.call(self, args);
+        }
 
-                    let res = self.step(body.clone())?;
+        if self.lookup_function(&func).is_none() {
+            if let Some(base) = func.strip_suffix('\'') {
+                self.derive(base)?;
+            }
+        }
 
-                    if let Some(temp) = temp {
-                        self.consts.insert(var.clone(), temp);
-                    } else {
-                        self.consts.remove(&var.clone());
-                    }
+        if let Some(closure) = self.lookup_function(&func) {
+            return self.call(&func, &closure, args);
+        }
 
-                    res
-                } else {
-                    anyhow::bail!("invalid function name: {}", func)
-                }
-            }
-        })
+        // `a(x + 1)` multiplies when a is a constant rather than a function
+        match (args.as_slice(), self.constants(func.clone())) {
+            ([x], Ok(con)) => binary(Operator::Mult, con, x.clone()),
+            _ => anyhow::bail!("invalid function name: {}", func),
+        }
+    }
+
+    // parameters shadow global functions, which shadow constants holding functions
+    pub fn lookup_function(&self, name: &str) -> Option<Rc<Closure>> {
+        if let Some(value) = self.scope.as_ref().and_then(|scope| scope.get(name)) {
+            return match value {
+                Value::Function(closure) => Some(closure.clone()),
+                _ => None,
+            };
+        }
+
+        if let Some((vars, body)) = self.funcs.get(name) {
+            return Some(Rc::new(
+                Closure::new(vars.clone(), body.clone(), None).named(name),
+            ));
+        }
+
+        match self.consts.get(name) {
+            Some(Value::Function(closure)) => Some(closure.clone()),
+            _ => None,
+        }
     }
 
-    fn constants(&self, con: String) -> anyhow::Result<f64> {
+    pub fn call(
+        &mut self,
+        func: &str,
+        closure: &Closure,
+        args: Vec<Value>,
+    ) -> anyhow::Result<Value> {
+        if closure.vars.len() != args.len() {
+            anyhow::bail!(
+                "{} takes {} arguments but {} were given",
+                func,
+                closure.vars.len(),
+                args.len()
+            );
+        }
+
+        if self.call_stack.len() >= self.recursion_limit {
+            anyhow::bail!(
+                "recursion limit of {} exceeded\n{}",
+                self.recursion_limit,
+                self.stack_trace()
+            );
+        }
+
+        // the body sees its parameters and the scope it was defined in, never the caller's
+        self.call_stack.push(func.to_owned());
+        let res = self.eval_in(
+            closure.scope.clone(),
+            &closure.vars,
+            args,
+            closure.body.clone(),
+        );
+        self.call_stack.pop();
+        res
+    }
+
+    fn stack_trace(&self) -> String {
+        let mut trace = String::from("most recent call first:");
+        for func in self.call_stack.iter().rev().take(STACK_TRACE_FRAMES) {
+            trace.push_str(&format!("\n    {}", func));
+        }
+        if self.call_stack.len() > STACK_TRACE_FRAMES {
+            trace.push_str(&format!(
+                "\n    ... {} more",
+                self.call_stack.len() - STACK_TRACE_FRAMES
+            ));
+        }
+        trace
+    }
+
+    // evaluates node with vars bound to args on top of the current scope
+    pub fn eval_with(
+        &mut self,
+        vars: &[String],
+        args: Vec<Value>,
+        node: Node,
+    ) -> anyhow::Result<Value> {
+        self.eval_in(self.scope.clone(), vars, args, node)
+    }
+
+    fn eval_in(
+        &mut self,
+        parent: Option<Rc<Scope>>,
+        vars: &[String],
+        args: Vec<Value>,
+        node: Node,
+    ) -> anyhow::Result<Value> {
+        let scope = Scope::new(vars.iter().cloned().zip(args).collect(), parent);
+        let outer = self.scope.replace(Rc::new(scope));
+        let res = self.step(node);
+        self.scope = outer;
+        res
+    }
+
+    fn constants(&self, con: String) -> anyhow::Result<Value> {
+        // parameters shadow everything else
+        if let Some(val) = self.scope.as_ref().and_then(|scope| scope.get(&con)) {
+            return Ok(val.clone());
+        }
+
         match con.as_str() {
-            "pi" => Ok(consts::PI),
-            "e" => Ok(consts::E),
-            "ans" => Ok(self.ans),
+            "pi" => Ok(consts::PI.into()),
+            "e" => Ok(consts::E.into()),
+            "i" => Ok(Value::Complex(Complex64::i())),
+            "ans" => Ok(self.ans.clone()),
             _ => {
                 if let Some(val) = self.consts.get(&con) {
-                    Ok(*val)
+                    Ok(val.clone())
+                } else if let Some(closure) = self.lookup_function(&con) {
+                    // a function used by name like `apply(f, 2)`
+                    Ok(Value::Function(closure))
+                } else if let Some(closure) = builtin_closure(&con) {
+                    Ok(Value::Function(Rc::new(closure)))
                 } else {
                     anyhow::bail!("invalid constant name: {}", con)
                 }
@@ -59,16 +468,114 @@ impl Interpreter {
         }
     }
 
-    pub fn run(&mut self, text: String) -> anyhow::Result<f64> {
+    // parameters and user constants, the names that can shadow a unit
+    fn is_bound(&self, name: &str) -> bool {
+        self.scope
+            .as_ref()
+            .is_some_and(|scope| scope.get(name).is_some())
+            || self.consts.contains_key(name)
+    }
+
+    // names of built-ins, constants and user functions starting with prefix, sorted
+    pub fn completions(&self, prefix: &str) -> Vec<String> {
+        let builtins = builtins::BUILTINS.iter().map(|builtin| builtin.name);
+        let names: BTreeSet<&str> = ["pi", "e", "i", "ans"]
+            .into_iter()
+            .chain(builtins)
+            .chain(self.funcs.keys().map(String::as_str))
+            .chain(self.consts.keys().map(String::as_str))
+            .filter(|name| name.starts_with(prefix))
+            .collect();
+        names.into_iter().map(str::to_owned).collect()
+    }
+
+    // runs every statement on the line and gives the last result,
+    // a line with nothing to run leaves ans as it is
+    pub fn run(&mut self, text: String) -> anyhow::Result<Value> {
         let mut parser = Parser::new(text.clone())?;
-        let node = parser.calc()?;
-        let res = self.step(node)?;
-        self.ans = res;
+        let statements = parser.calc()?;
+        if statements.is_empty() {
+            return Ok(self.ans.clone());
+        }
+        // later statements see the results of earlier ones, but a line that fails is undone
+        // as a whole so it never half runs, and it is not saved
+        let snapshot = (self.ans.clone(), self.consts.clone(), self.funcs.clone());
+        for node in statements {
+            match self.step(node) {
+                // builtins and folds build integers without going through an operator
+                Ok(value) => self.ans = wrap(self.int_width, value),
+                Err(err) => {
+                    (self.ans, self.consts, self.funcs) = snapshot;
+                    return Err(err);
+                }
+            }
+        }
         self.executed_lines.push(text.trim().to_owned());
-        Ok(res)
+        Ok(self.ans.clone())
+    }
+
+    // derivatives of trig functions depend on the mode so they are derived again when needed
+    pub fn set_angle_mode(&mut self, mode: AngleMode) {
+        self.angle_mode = mode;
+        self.funcs.retain(|name, _| !name.ends_with('\''));
+        self.executed_lines.push(format!("!{}", mode.unit()));
+    }
+
+    pub fn set_output_base(&mut self, base: &str) -> anyhow::Result<()> {
+        let Ok(base) = base.parse() else {
+            anyhow::bail!("the base must be 2, 8, 10 or 16 but got {}", base);
+        };
+        bits::prefix(base)?;
+        self.output_base = base;
+        self.executed_lines.push(format!("!base {}", base));
+        Ok(())
+    }
+
+    pub fn set_recursion_limit(&mut self, limit: &str) -> anyhow::Result<()> {
+        let limit = limit.parse()?;
+        if limit > MAX_RECURSION_LIMIT {
+            anyhow::bail!(
+                "the recursion limit can be at most {} but got {}",
+                MAX_RECURSION_LIMIT,
+                limit
+            );
+        }
+        self.recursion_limit = limit;
+        Ok(())
+    }
+
+    // `off` goes back to integers of any size
+    pub fn set_int_width(&mut self, width: &str) -> anyhow::Result<()> {
+        self.int_width = match width {
+            "off" => None,
+            width => Some(IntWidth::parse(width)?),
+        };
+        self.executed_lines.push(format!("!width {}", width));
+        Ok(())
     }
 
-    pub fn run_file(&mut self, path: PathBuf) -> anyhow::Result<(Vec<f64>, f64)> {
+    // `!deg`, `!base 16` or `!width u8`, the modes a saved session replays along with its lines
+    pub fn run_command(&mut self, line: &str) -> Option<anyhow::Result<()>> {
+        if let Some(mode) = AngleMode::from_command(line) {
+            self.set_angle_mode(mode);
+            return Some(Ok(()));
+        }
+        let command = line.split('#').next().unwrap_or_default().trim();
+        if let Some(base) = command.strip_prefix("!base ") {
+            return Some(self.set_output_base(base.trim()));
+        }
+        if let Some(width) = command.strip_prefix("!width ") {
+            return Some(self.set_int_width(width.trim()));
+        }
+        None
+    }
+
+    // the result as the REPL prints it, with integers in the output base
+    pub fn display(&self, value: &Value) -> String {
+        bits::InBase(value, self.output_base).to_string()
+    }
+
+    pub fn run_file(&mut self, path: PathBuf) -> anyhow::Result<(Vec<Value>, Value)> {
         let contents = fs::read_to_string(path)?;
 
         let mut interpreter = self.clone();
@@ -76,8 +583,15 @@ impl Interpreter {
         let mut debug_out = Vec::new();
 
         for line in contents.split('\n') {
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(res) = interpreter.run_command(line) {
+                res?;
+                continue;
+            }
             let mut line = line.to_owned();
-            let do_out = line.chars().next() == Some('!');
+            let do_out = line.starts_with('!');
             if do_out {
                 line.remove(0);
             }
@@ -88,32 +602,117 @@ impl Interpreter {
         }
 
         *self = interpreter;
-        
-        Ok((debug_out, self.ans))
+
+        Ok((debug_out, self.ans.clone()))
     }
 
-    fn step(&mut self, node: Node) -> anyhow::Result<f64> {
+    pub fn step(&mut self, node: Node) -> anyhow::Result<Value> {
         Ok(match node {
-            Node::Number(num) => num,
+            Node::Number(num) => wrap(self.int_width, num),
+            // names in scope win over units, so `2m` is a product inside `v(m) = 2m`
+            Node::Quantity(num, unit) => {
+                match Node::quantity_as_product(&num, &unit, |name| self.is_bound(name)) {
+                    Some(product) => self.step(product)?,
+                    None => Value::Quantity(Box::new(num), unit),
+                }
+            }
+            Node::Convert(node, unit) => self.step(*node)?.convert(&unit)?,
             Node::Expr(node1, op, node2) => match op {
-                crate::ast::Operator::Plus => self.step(*node1)? + self.step(*node2)?,
-                crate::ast::Operator::Minus => self.step(*node1)? - self.step(*node2)?,
-                crate::ast::Operator::Mult => self.step(*node1)? * self.step(*node2)?,
-                crate::ast::Operator::Div => self.step(*node1)? / self.step(*node2)?,
-                crate::ast::Operator::Pow => self.step(*node1)?.powf(self.step(*node2)?),
+                // the right side is only evaluated when it decides the result
+                Operator::And => {
+                    Value::from_bool(self.step(*node1)?.is_true()? && self.step(*node2)?.is_true()?)
+                }
+                Operator::Or => {
+                    Value::from_bool(self.step(*node1)?.is_true()? || self.step(*node2)?.is_true()?)
+                }
+                op => wrap(
+                    self.int_width,
+                    binary(op, self.step(*node1)?, self.step(*node2)?)?,
+                ),
+            },
+            Node::Unary(op, node) => match op {
+                crate::ast::UnaryOperator::Plus => number(self.step(*node)?)?,
+                crate::ast::UnaryOperator::Minus => {
+                    wrap(self.int_width, -number(self.step(*node)?)?)
+                }
+                crate::ast::UnaryOperator::Not => Value::from_bool(!self.step(*node)?.is_true()?),
+                crate::ast::UnaryOperator::BitNot => {
+                    wrap(self.int_width, bits::not(self.step(*node)?)?)
+                }
+            },
+            Node::List(items) => Value::List(
+                items
+                    .into_iter()
+                    .map(|item| self.step(item))
+                    .collect::<anyhow::Result<_>>()?,
+            ),
+            Node::Matrix(rows) => Value::Matrix(Matrix::new(
+                rows.into_iter()
+                    .map(|row| row.into_iter().map(|item| self.step(item)).collect())
+                    .collect::<anyhow::Result<_>>()?,
+            )?),
+            Node::Range(start, end) => range(self.step(*start)?, self.step(*end)?)?,
+            Node::Index(list, index) => match self.step(*list)? {
+                Value::List(items) => element(&items, self.step(*index)?)?,
+                value => anyhow::bail!("can not index {}", value),
             },
-            Node::Function(func, node) => {
-                let val = self.step(*node)?;
-                self.functions(func, val)?
+            Node::Lambda(vars, body) => {
+                Value::Function(Rc::new(Closure::new(vars, *body, self.scope.clone())))
+            }
+            Node::If(cond, then, otherwise) => {
+                if self.step(*cond)?.is_true()? {
+                    self.step(*then)?
+                } else {
+                    self.step(*otherwise)?
+                }
+            }
+            Node::Function(func, nodes) => {
+                // solve, integrate and the like bind variables in their unevaluated arguments
+                if let Some(Builtin {
+                    eval: Eval::Nodes(eval),
+                    ..
+                }) = builtins::find(&func)
+                {
+                    return eval(self, &nodes);
+                }
+
+                let args = nodes
+                    .into_iter()
+                    .map(|node| self.step(node))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                self.functions(func, args)?
             }
             Node::AssignConst(name, expr) => {
-                let val = self.step(*expr)?;
-                self.consts.insert(name, val);
+                // a lambda stored in a constant is known by its name
+                let val = match self.step(*expr)? {
+                    Value::Function(closure) if closure.name.is_none() => {
+                        Value::Function(Rc::new(closure.as_ref().clone().named(&name)))
+                    }
+                    val => val,
+                };
+                match &mut self.scope {
+                    Some(scope) => {
+                        Rc::make_mut(scope).vars.insert(name, val.clone());
+                    }
+                    None => {
+                        self.consts.insert(name, val.clone());
+                    }
+                }
                 val
             }
-            Node::AssignFunc(name, var, body) => {
-                self.funcs.insert(name, (var, *body));
-                0.
+            Node::AssignFunc(name, vars, body) => {
+                if let Some((i, var)) = vars
+                    .iter()
+                    .enumerate()
+                    .find(|(i, var)| vars[..*i].contains(var))
+                {
+                    anyhow::bail!("duplicate parameter {} at position {}", var, i + 1);
+                }
+                // derivatives of the old definition are stale
+                self.funcs
+                    .retain(|func, _| func.trim_end_matches('\'') != name || func == &name);
+                self.funcs.insert(name, (vars, *body));
+                Value::default()
             }
             Node::Const(const_name) => self.constants(const_name)?,
         })
@@ -1,9 +1,60 @@
-use crate::token::{Token, TokenType};
+use crate::{
+    token::{Token, TokenType},
+    unit::Unit,
+};
+
+// longer symbols come first so `<<` is not read as two `<`, the unicode ones are aliases
+const SYMBOLS: &[(&str, TokenType)] = &[
+    ("->", TokenType::Arrow),
+    ("<<", TokenType::ShiftLeft),
+    (">>", TokenType::ShiftRight),
+    ("<=", TokenType::LessEq),
+    (">=", TokenType::GreaterEq),
+    ("==", TokenType::Equal),
+    ("!=", TokenType::NotEqual),
+    ("..", TokenType::Range),
+    ("+", TokenType::Add),
+    ("-", TokenType::Sub),
+    ("−", TokenType::Sub),
+    ("*", TokenType::Mult),
+    ("×", TokenType::Mult),
+    ("·", TokenType::Mult),
+    ("/", TokenType::Div),
+    ("÷", TokenType::Div),
+    ("^", TokenType::Exp),
+    ("√", TokenType::Sqrt),
+    ("(", TokenType::LParen),
+    (")", TokenType::RParen),
+    ("[", TokenType::LBracket),
+    ("]", TokenType::RBracket),
+    ("=", TokenType::Assign),
+    ("<", TokenType::Less),
+    (">", TokenType::Greater),
+    ("'", TokenType::Prime),
+    (",", TokenType::Comma),
+    (";", TokenType::Semicolon),
+    ("&", TokenType::BitAnd),
+    ("|", TokenType::BitOr),
+    ("~", TokenType::Tilde),
+];
+
+// names start with a letter or `_` and may contain digits after that, like `x1` or `v_0`
+fn is_identifier_start(char: char) -> bool {
+    char.is_alphabetic() || char == '_'
+}
+
+fn is_identifier_char(char: char) -> bool {
+    char.is_alphanumeric() || char == '_'
+}
 
 pub struct Lexer {
     text: Vec<char>,
     pos: usize,
     current_char: Option<char>,
+    // a unit may directly follow a number
+    after_number: bool,
+    // a unit must follow `to`
+    expect_unit: bool,
 }
 
 impl Lexer {
@@ -13,9 +64,16 @@ impl Lexer {
             text: text.chars().collect(),
             pos: 0,
             current_char,
+            after_number: false,
+            expect_unit: false,
         }
     }
 
+    fn seek(&mut self, pos: usize) {
+        self.pos = pos;
+        self.current_char = self.text.get(pos).copied();
+    }
+
     fn advance(&mut self) {
         self.pos += 1;
 
@@ -26,8 +84,15 @@ impl Lexer {
         }
     }
 
+    fn at(&self, symbol: &str) -> bool {
+        symbol
+            .chars()
+            .enumerate()
+            .all(|(i, char)| self.text.get(self.pos + i) == Some(&char))
+    }
+
     fn skip_whitespace(&mut self) {
-        while self.current_char.map_or(false, |char| char.is_whitespace()) {
+        while self.current_char.is_some_and(|char| char.is_whitespace()) {
             self.advance();
         }
     }
@@ -36,103 +101,248 @@ impl Lexer {
         let mut result = String::default();
         let pos = self.pos;
 
-        while self.current_char.map_or(false, |char| char.is_alphabetic()) {
+        while self.current_char.is_some_and(is_identifier_char) {
             result.push(self.current_char.unwrap());
             self.advance();
         }
+        if result == "π" {
+            result = String::from("pi");
+        }
+
+        let token = match result.as_str() {
+            "to" => {
+                self.expect_unit = true;
+                TokenType::To
+            }
+            "and" => TokenType::And,
+            "or" => TokenType::Or,
+            "not" => TokenType::Not,
+            "if" => TokenType::If,
+            "then" => TokenType::Then,
+            "else" => TokenType::Else,
+            "xor" => TokenType::Xor,
+            _ => TokenType::Ident,
+        };
 
-        Token::new(result, TokenType::Ident, pos)
+        Token::new(result, token, pos)
     }
 
-    fn number(&mut self) -> Token {
+    // reads the longest run of `name^exp` parts joined by `*` or `/` that is a known unit
+    fn unit(&mut self) -> Option<Token> {
+        let start = self.pos;
+        let mut end = start;
         let mut result = String::default();
-        let pos = self.pos;
 
-        if self.current_char == Some('-') {
-            result.push('-');
-            self.advance();
+        loop {
+            let mut part = String::default();
+            if !result.is_empty() {
+                match self.current_char {
+                    Some(op @ ('*' | '/')) => {
+                        part.push(op);
+                        self.advance();
+                    }
+                    _ => break,
+                }
+            }
+
+            if !self.current_char.is_some_and(|char| char.is_alphabetic()) {
+                break;
+            }
+            while self.current_char.is_some_and(|char| char.is_alphabetic()) {
+                part.push(self.current_char.unwrap());
+                self.advance();
+            }
+            // `s1` and `m_0` are names rather than units
+            if self.current_char.is_some_and(is_identifier_char) {
+                break;
+            }
+
+            let exp_pos = self.pos;
+            if self.current_char == Some('^') {
+                let mut exp = String::from("^");
+                self.advance();
+                if self.current_char == Some('-') {
+                    exp.push('-');
+                    self.advance();
+                }
+                if self.current_char.is_some_and(|char| char.is_ascii_digit()) {
+                    while self.current_char.is_some_and(|char| char.is_ascii_digit()) {
+                        exp.push(self.current_char.unwrap());
+                        self.advance();
+                    }
+                    part.push_str(&exp);
+                } else {
+                    self.seek(exp_pos);
+                }
+            }
+
+            let candidate = format!("{}{}", result, part);
+            if Unit::parse(&candidate).is_err() {
+                break;
+            }
+            result = candidate;
+            end = self.pos;
         }
 
-        while self.current_char.map_or(false, |char| char.is_ascii_digit()) {
-            result.push(self.current_char.unwrap());
+        self.seek(end);
+        (!result.is_empty()).then(|| Token::new(result, TokenType::Unit, start))
+    }
+
+    // points at the character at pos below the input, like the parser's syntax errors
+    fn error(&self, pos: usize, message: &str) -> anyhow::Error {
+        let text: String = self.text.iter().collect();
+        anyhow::anyhow!("{}\n{}\n{}^", message, text.trim_end(), " ".repeat(pos))
+    }
+
+    // a run of digits where `_` may separate two of them, the separators are left out
+    fn digits(&mut self, radix: u32, result: &mut String) -> anyhow::Result<()> {
+        let mut after_digit = false;
+        while let Some(char) = self.current_char {
+            if char == '_' {
+                let next = self.text.get(self.pos + 1);
+                if !after_digit || !next.is_some_and(|next| next.is_digit(radix)) {
+                    return Err(self.error(self.pos, "a digit separator must be between digits"));
+                }
+            } else if char.is_digit(radix) {
+                result.push(char);
+            } else {
+                break;
+            }
+            after_digit = char != '_';
             self.advance();
         }
+        Ok(())
+    }
+
+    fn radix_number(&mut self, radix: u32) -> anyhow::Result<Token> {
+        let pos = self.pos;
+        let mut result: String = self.text[pos..pos + 2].iter().collect();
+        self.seek(pos + 2);
+        self.digits(radix, &mut result)?;
 
-        if self.current_char == Some('.') {
+        // `0xfg` or `0b102` is a typo rather than a number times a name
+        if result.len() == 2 || self.current_char.is_some_and(|char| char.is_alphanumeric()) {
+            let name = match radix {
+                2 => "binary",
+                8 => "octal",
+                _ => "hex",
+            };
+            return Err(self.error(self.pos, &format!("expected a {} digit", name)));
+        }
+        Ok(Token::new(result, TokenType::Number, pos))
+    }
+
+    // `12`, `1_000`, `0.5`, `.5` and `6.022e23`, or hex, binary and octal after `0x`, `0b` and `0o`
+    fn number(&mut self) -> anyhow::Result<Token> {
+        let pos = self.pos;
+        if self.current_char == Some('0') {
+            match self.text.get(pos + 1) {
+                Some('x') => return self.radix_number(16),
+                Some('b') => return self.radix_number(2),
+                Some('o') => return self.radix_number(8),
+                _ => {}
+            }
+        }
+
+        let mut result = String::default();
+        self.digits(10, &mut result)?;
+
+        // `1..3` is a range rather than a decimal point
+        if self.current_char == Some('.') && self.text.get(self.pos + 1) != Some(&'.') {
             result.push('.');
             self.advance();
+            if !self.current_char.is_some_and(|char| char.is_ascii_digit()) {
+                return Err(self.error(self.pos, "expected a digit after the decimal point"));
+            }
+            self.digits(10, &mut result)?;
         }
 
-        while self.current_char.map_or(false, |char| char.is_ascii_digit()) {
-            result.push(self.current_char.unwrap());
+        // `2e3` is 2000 but `2e` and `2 e` are 2 times e
+        let is_digit = |i: usize| self.text.get(i).is_some_and(|char| char.is_ascii_digit());
+        let exponent = match self.current_char {
+            Some('e' | 'E') if is_digit(self.pos + 1) => Some(1),
+            Some('e' | 'E')
+                if matches!(self.text.get(self.pos + 1), Some('+' | '-'))
+                    && is_digit(self.pos + 2) =>
+            {
+                Some(2)
+            }
+            _ => None,
+        };
+        if let Some(len) = exponent {
+            result.push('e');
             self.advance();
+            if len == 2 {
+                result.push(self.current_char.unwrap_or_default());
+                self.advance();
+            }
+            self.digits(10, &mut result)?;
         }
 
-        Token::new(result, TokenType::Number, pos)
+        if self.current_char == Some('.') && self.text.get(self.pos + 1) != Some(&'.') {
+            return Err(self.error(self.pos, "a number can only have one decimal point"));
+        }
+        Ok(Token::new(result, TokenType::Number, pos))
     }
 
     pub fn get_next_token(&mut self) -> anyhow::Result<Token> {
-        loop {
-            if let Some(current_char) = self.current_char {
-                match current_char {
-                    '+' => {
-                        self.advance();
-                        return Ok(Token::new(String::from("+"), TokenType::Add, self.pos));
-                    }
-                    '-' => {
-                        self.advance();
-                        return Ok(Token::new(String::from("-"), TokenType::Sub, self.pos));
-                    }
-                    '*' => {
-                        self.advance();
-                        return Ok(Token::new(String::from("*"), TokenType::Mult, self.pos));
-                    }
-                    '/' => {
-                        self.advance();
-                        return Ok(Token::new(String::from("/"), TokenType::Div, self.pos));
-                    }
-                    '^' => {
-                        self.advance();
-                        return Ok(Token::new(String::from("^"), TokenType::Exp, self.pos));
-                    }
-                    '(' => {
-                        self.advance();
-                        return Ok(Token::new(String::from("("), TokenType::LParen, self.pos));
-                    }
-                    ')' => {
-                        self.advance();
-                        return Ok(Token::new(String::from(")"), TokenType::RParen, self.pos));
-                    }
-                    '=' => {
-                        self.advance();
-                        return Ok(Token::new(String::from("="), TokenType::Assign, self.pos));
-                    }
-                    _ => {}
-                };
+        if std::mem::take(&mut self.expect_unit) {
+            self.skip_whitespace();
+            return match self.unit() {
+                Some(token) => Ok(token),
+                None => Err(self.error(self.pos, "Expected a unit after to")),
+            };
+        }
 
-                if current_char.is_whitespace() {
-                    self.skip_whitespace();
-                    continue;
+        // a name followed by `(` is a call, so `2 h(3)` is not 2 hours times 3
+        if std::mem::take(&mut self.after_number) {
+            let pos = self.pos;
+            self.skip_whitespace();
+            if let Some(token) = self.unit() {
+                if self.current_char != Some('(') {
+                    return Ok(token);
                 }
+            }
+            self.seek(pos);
+        }
 
-                if current_char.is_ascii_digit() || current_char == '-' {
-                    return Ok(self.number());
-                }
+        while let Some(current_char) = self.current_char {
+            if current_char.is_whitespace() {
+                self.skip_whitespace();
+                continue;
+            }
 
-                if current_char.is_alphabetic() {
-                    return Ok(self.identifier());
+            // `#` starts a comment that runs to the end of the line
+            if current_char == '#' {
+                while self.current_char.is_some_and(|char| char != '\n') {
+                    self.advance();
                 }
+                continue;
+            }
 
-                anyhow::bail!("Invalid character: {}", current_char);
-            } else {
-                break;
+            let start = self.pos;
+            if let Some((symbol, token)) = SYMBOLS.iter().find(|(symbol, _)| self.at(symbol)) {
+                self.seek(start + symbol.chars().count());
+                return Ok(Token::new(symbol.to_string(), token.clone(), start));
             }
+
+            let leading_dot = current_char == '.'
+                && self
+                    .text
+                    .get(self.pos + 1)
+                    .is_some_and(|char| char.is_ascii_digit());
+            if current_char.is_ascii_digit() || leading_dot {
+                self.after_number = true;
+                return self.number();
+            }
+
+            if is_identifier_start(current_char) {
+                return Ok(self.identifier());
+            }
+
+            return Err(self.error(self.pos, &format!("Invalid character: {}", current_char)));
         }
-        Ok(Token::new(
-            "".to_owned(),
-            TokenType::EOI,
-            self.text.len() - 1,
-        ))
+        Ok(Token::new("".to_owned(), TokenType::Eoi, self.text.len()))
     }
 
     pub fn get_all_tokens(&mut self) -> anyhow::Result<Vec<Token>> {
@@ -140,11 +350,10 @@ impl Lexer {
         loop {
             let token = self.get_next_token()?;
             res.push(token.clone());
-            if token.token == TokenType::EOI {
+            if token.token == TokenType::Eoi {
                 break;
             }
         }
         Ok(res)
     }
-
 }
@@ -1,14 +1,42 @@
-use std::{env, fs, io::Write, path::PathBuf};
+use std::{env, fs, path::PathBuf};
 
-use crate::interpreter::Interpreter;
+use rustyline::{error::ReadlineError, history::DefaultHistory, Editor};
+
+use crate::{completion::Completion, interpreter::Interpreter};
 
 mod ast;
+mod bits;
+mod builtins;
+mod completion;
+mod derivative;
 mod interpreter;
 mod lexer;
+mod matrix;
+mod numeric;
 mod parser;
+mod scope;
+mod simplify;
+mod special;
+mod stats;
 mod token;
+mod unit;
+mod value;
+
+// deeply recursive user functions need far more stack than the main thread gets, enough
+// for the deepest recursion limit that can be set
+const STACK_SIZE: usize = interpreter::MAX_RECURSION_LIMIT * interpreter::STACK_PER_CALL;
 
 fn main() {
+    let calc = std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(run)
+        .expect("failed to start the calculator thread");
+    if calc.join().is_err() {
+        std::process::exit(1);
+    }
+}
+
+fn run() {
     let args: Vec<String> = env::args().collect();
     let mut file: Option<PathBuf> = None;
     if let Some(flag) = args.get(1) {
@@ -56,42 +84,74 @@ fn main() {
         match interpreter.run_file(path) {
             Ok((debug_out, res)) => {
                 for out in debug_out {
-                    println!("! {}", out);
+                    println!("! {}", interpreter.display(&out));
                 }
-                println!("{}", res);
+                println!("{}", interpreter.display(&res));
             }
             Err(err) => eprintln!("err: {}", err),
         }
-        return;
     } else {
-        let mut text = String::new();
+        let mut editor: Editor<Completion, DefaultHistory> = match Editor::new() {
+            Ok(editor) => editor,
+            Err(err) => {
+                eprintln!("err: {}", err);
+                return;
+            }
+        };
+        editor.set_helper(Some(Completion::default()));
 
         println!("Welcome To CL Calc a command line calculator tool:\nEnter \"!exit\" to exit or \"!help\" for additional help.\nRun with \"-?\" to see valid arguments.");
 
         loop {
-            text.clear();
-            print!("calc> ");
-            std::io::stdout().flush().unwrap();
-            std::io::stdin().read_line(&mut text).unwrap();
-            if text.trim().is_empty() {
-                text.clear();
+            if let Some(completion) = editor.helper_mut() {
+                completion.names = interpreter.completions("");
+            }
+            let text = match editor.readline("calc> ") {
+                Ok(text) => text,
+                Err(ReadlineError::Eof | ReadlineError::Interrupted) => break,
+                Err(err) => {
+                    eprintln!("err: {}", err);
+                    break;
+                }
+            };
+            // blank lines and comments print nothing
+            if text.trim().is_empty() || text.trim().starts_with('#') {
                 continue;
             }
+            let _ = editor.add_history_entry(text.as_str());
 
             if text.trim() == "!exit" {
                 break;
             }
 
             if text.trim() == "!help" {
-                println!("The built in functions are:\n- sqrt(x)\n- ln(x)\n- abs(x)\n- cos(x)\n- sin(x)\n- tan(x)\n- log(x)");
-                println!("You can define custom functions with name(x) = expression.");
+                println!("The built in functions are:");
+                for builtin in builtins::BUILTINS {
+                    println!("- {}: {}", builtin.usage, builtin.doc);
+                }
+                println!("You can define custom functions with name(x) = expression or name(x, y, ...) = expression.");
+                println!("Anonymous functions are written x -> expression or (x, y, ...) -> expression and can be stored, passed to functions, or given to solve, integrate, sum and prod in place of an expression and a variable.");
                 println!(
-                    "The built in constants are:\n- pi: {}\n- e: {}\n- ans: the result of the previous calculation",
+                    "The built in constants are:\n- pi: {}\n- e: {}\n- i: the imaginary unit\n- ans: the result of the previous calculation",
                     std::f64::consts::PI,
                     std::f64::consts::E
                 );
                 println!("You can define custom constants with name = expression.");
+                println!("Numbers can have units like 3 m/s or 20 min and be converted with expression to unit.");
+                println!("Lists are written [1, 2, 3] or as the inclusive range 1..3, indexed from 0 with xs[0] and xs[-1] for the last element, and + - * / ^ work element by element.");
+                println!("Matrices are written [1, 2; 3, 4] with rows split by ;, and * multiplies matrices, or a matrix and a list used as a vector.");
+                println!("Comparisons (< <= == != > >=), and, or and not give 1 or 0, and if cond then a else b only evaluates the branch it takes.");
                 println!("You can enter !vars to see custom functions and constants.");
+                println!("Press Tab to complete a name, or enter !complete <prefix> to list the functions and constants starting with prefix.");
+                println!("You can enter !diff <name> or diff(name) to define name' as the derivative of a function, and call it with name'(x).");
+                println!("You can enter !deg, !rad or !grad to read plain numbers given to trig functions, and returned by their inverses, as degrees, radians or gradians. The same lines work in a file, and a unit like 90deg ignores the mode.");
+                println!("Integers can be written in hex 0x1f, binary 0b101 or octal 0o17, and combined with & | xor << >> and ~.");
+                println!(
+                    "You can enter !base <2, 8, 10 or 16> to print integer results in that base."
+                );
+                println!("You can enter !width <u8 to i64> to wrap integer results to that type like a programmer's calculator, or !width off to stop.");
+                println!("You can enter !limit <n> to change how deeply functions can call themselves, the default is {} and at most {}.", interpreter::DEFAULT_RECURSION_LIMIT, interpreter::MAX_RECURSION_LIMIT);
+                println!("Everything after # is a comment, and ; separates several calculations on one line.");
                 println!("You can run CL Calc followed by a path to run a file to run a list of calculations.");
                 println!("You can enter !file <path> to run a list of calculations.");
                 println!("You can enter !out <path> to output all successfully run commands.");
@@ -100,8 +160,8 @@ fn main() {
 
             if text.trim() == "!vars" {
                 println!("Functions:");
-                for (function, (var, _)) in interpreter.funcs.iter() {
-                    println!("    {}({})", function, var);
+                for (function, (vars, body)) in interpreter.funcs.iter() {
+                    println!("    {}({}) = {}", function, vars.join(", "), body);
                 }
                 if interpreter.funcs.is_empty() {
                     println!("    None");
@@ -116,13 +176,43 @@ fn main() {
                 continue;
             }
 
+            if let Some(prefix) = text.trim().strip_prefix("!complete") {
+                println!("{}", interpreter.completions(prefix.trim()).join("  "));
+                continue;
+            }
+
+            if let Some(func) = text.trim().strip_prefix("!diff ") {
+                match interpreter.derive(func.trim()) {
+                    Ok(name) => {
+                        let (vars, body) = &interpreter.funcs[&name];
+                        println!("{}({}) = {}", name, vars.join(", "), body);
+                    }
+                    Err(err) => eprintln!("err: {}", err),
+                }
+                continue;
+            }
+
+            if let Some(res) = interpreter.run_command(&text) {
+                if let Err(err) = res {
+                    eprintln!("err: {}", err);
+                }
+                continue;
+            }
+
+            if let Some(limit) = text.trim().strip_prefix("!limit ") {
+                if let Err(err) = interpreter.set_recursion_limit(limit.trim()) {
+                    eprintln!("err: {}", err);
+                }
+                continue;
+            }
+
             if text.trim().get(0..6) == Some("!file ") {
                 match interpreter.run_file(text.trim().get(6..).unwrap_or_default().into()) {
                     Ok((debug_out, res)) => {
                         for out in debug_out {
-                            println!("! {}", out);
+                            println!("! {}", interpreter.display(&out));
                         }
-                        println!("{}", res);
+                        println!("{}", interpreter.display(&res));
                     }
                     Err(err) => eprintln!("err: {}", err),
                 }
@@ -132,7 +222,7 @@ fn main() {
 
             if text.trim().get(0..5) == Some("!out ") {
                 let path: PathBuf =
-                    if let Some(Ok(path)) = text.trim().get(5..).map(|arg| arg.try_into()) {
+                    if let Some(Ok(path)) = text.trim().get(5..).map(|arg| arg.parse()) {
                         path
                     } else {
                         eprintln!("err: No Path");
@@ -146,14 +236,14 @@ fn main() {
                 continue;
             }
 
-            if text.trim().chars().next() == Some('!') {
+            if text.trim().starts_with('!') {
                 println!("err: Invalid Command {}", text);
                 continue;
             }
 
             match interpreter.run(text.clone()) {
                 Ok(result) => {
-                    println!("{}", result);
+                    println!("{}", interpreter.display(&result));
                 }
                 Err(err) => {
                     eprintln!("err: {}", err);
@@ -166,12 +256,892 @@ fn main() {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{interpreter::AngleMode, value::Value};
+    use rustyline::completion::Completer;
 
     #[test]
     fn interpreter_file() {
         let mut inter = Interpreter::default();
         let result = inter.run_file("example.calc".into()).unwrap();
-        assert_eq!(result.0[0], -1.5574077246549018);
-        assert_eq!(result.1, 0.6180339887498948);
+        assert_eq!(result.0[0], Value::from(-1.5574077246549018));
+        assert_eq!(result.1, Value::from(0.6180339887498948));
+    }
+
+    #[test]
+    fn multi_argument_functions() {
+        let mut inter = Interpreter::default();
+        inter.run("hyp(a, b) = sqrt(a^2 + b^2)".into()).unwrap();
+        assert_eq!(inter.run("hyp(3, 4)".into()).unwrap(), Value::from(5));
+        assert_eq!(inter.run("max(1, 7, 3)".into()).unwrap(), Value::from(7));
+        assert_eq!(inter.run("log(8, 2)".into()).unwrap(), Value::from(3));
+        assert!(inter.run("hyp(1)".into()).is_err());
+    }
+
+    #[test]
+    fn operator_precedence() {
+        let mut inter = Interpreter::default();
+        assert_eq!(inter.run("2-3".into()).unwrap(), Value::from(-1));
+        assert_eq!(inter.run("-2^2".into()).unwrap(), Value::from(-4));
+        assert_eq!(inter.run("2^-1".into()).unwrap(), Value::from(0.5));
+        assert_eq!(inter.run("-(1+2) * +2".into()).unwrap(), Value::from(-6));
+        assert_eq!(inter.run("--3".into()).unwrap(), Value::from(3));
+        assert_eq!(inter.run("2^3^2".into()).unwrap(), Value::from(512));
+        assert_eq!(
+            inter.run("8 / 4 / 2 - 1 - 1".into()).unwrap(),
+            Value::from(-1)
+        );
+    }
+
+    #[test]
+    fn exact_arithmetic() {
+        let mut inter = Interpreter::default();
+        assert_eq!(inter.run("0.1 + 0.2".into()).unwrap().to_string(), "0.3");
+        assert_eq!(inter.run("1/3*3".into()).unwrap().to_string(), "1");
+        assert_eq!(inter.run("1/3".into()).unwrap().to_string(), "1/3");
+        assert_eq!(inter.run("2^-2".into()).unwrap().to_string(), "0.25");
+        assert_eq!(inter.run("sqrt(9/4)".into()).unwrap().to_string(), "1.5");
+        assert!(inter.run("1/0".into()).is_err());
+    }
+
+    #[test]
+    fn complex_numbers() {
+        let mut inter = Interpreter::default();
+        assert_eq!(inter.run("sqrt(-4)".into()).unwrap().to_string(), "0 + 2i");
+        assert_eq!(inter.run("i^2".into()).unwrap(), Value::from(-1));
+        assert_eq!(
+            inter.run("(1 + 2*i) * (1 - 2*i)".into()).unwrap(),
+            Value::from(5)
+        );
+        assert_eq!(inter.run("abs(3 + 4*i)".into()).unwrap(), Value::from(5));
+        assert_eq!(
+            inter.run("conj(1 + i)".into()).unwrap().to_string(),
+            "1 - 1i"
+        );
+        assert_eq!(
+            inter.run("im(ln(-1))".into()).unwrap(),
+            Value::from(std::f64::consts::PI)
+        );
+    }
+
+    #[test]
+    fn units() {
+        let mut inter = Interpreter::default();
+        assert_eq!(
+            inter.run("3 m/s * 20 min".into()).unwrap().to_string(),
+            "3600 m"
+        );
+        assert_eq!(inter.run("3 km + 2 km".into()).unwrap().to_string(), "5 km");
+        assert_eq!(inter.run("1 km / 1 m".into()).unwrap(), Value::from(1000));
+        assert_eq!(
+            inter.run("2 kg*m/s^2 to N".into()).unwrap().to_string(),
+            "2 N"
+        );
+        assert!(inter.run("1 m + 2 s".into()).is_err());
+        assert_eq!(
+            inter.run("5 km to mi".into()).unwrap().to_string(),
+            "3.1068559611866697 mi"
+        );
+    }
+
+    #[test]
+    fn derivatives() {
+        let mut inter = Interpreter::default();
+        inter.run("f(x) = x^3 + sin(x)".into()).unwrap();
+        inter.run("g(x) = f(2*x)".into()).unwrap();
+        assert_eq!(inter.run("f'(0)".into()).unwrap(), Value::from(1.));
+        assert_eq!(inter.run("g'(0)".into()).unwrap(), Value::from(2.));
+        assert_eq!(
+            inter.run("f''(1)".into()).unwrap(),
+            Value::from(6. - 1f64.sin())
+        );
+
+        inter.run("f(x) = x".into()).unwrap();
+        assert_eq!(inter.run("f'(5)".into()).unwrap(), Value::from(1));
+
+        // recursive functions with several arguments can not be inlined
+        inter
+            .run("p(x, n) = if n <= 0 then 1 else x * p(x, n - 1)".into())
+            .unwrap();
+        inter.run("q(x) = p(x, 3)".into()).unwrap();
+        assert!(inter.run("q'(1)".into()).is_err());
+        inter.run("a(x, y) = x * y".into()).unwrap();
+        inter.run("b(x) = a(x, x)".into()).unwrap();
+        assert_eq!(inter.run("b'(2)".into()).unwrap(), Value::from(4));
+    }
+
+    #[test]
+    fn pretty_print_and_simplify() {
+        let mut inter = Interpreter::default();
+        inter.run("f(x) = tan(x) + 1".into()).unwrap();
+        inter
+            .run("g(x) = -(x - 1) - (2 - x)^2^x / 3".into())
+            .unwrap();
+        inter.run("h(x) = x*x*3 - x + 2*x - 5".into()).unwrap();
+        assert_eq!(inter.funcs["f"].1.to_string(), "tan(x) + 1");
+        assert_eq!(inter.funcs["g"].1.to_string(), "-(x - 1) - (2 - x)^2^x / 3");
+
+        inter.derive("f").unwrap();
+        inter.derive("h").unwrap();
+        assert_eq!(inter.funcs["f'"].1.to_string(), "1 / cos(x)^2");
+        assert_eq!(inter.funcs["h'"].1.to_string(), "6 * x + 1");
+
+        // quotients with the same divisor are like terms
+        inter.run("q(x) = x^2 * x^-2".into()).unwrap();
+        inter.run("r(x) = x - 2/x".into()).unwrap();
+        inter.derive("q").unwrap();
+        inter.derive("r").unwrap();
+        assert_eq!(inter.funcs["q'"].1.to_string(), "0");
+        assert_eq!(inter.funcs["r'"].1.to_string(), "2 / x^2 + 1");
+
+        // matrix products do not commute so factors keep their order
+        inter.run("a = [1, 2; 3, 4]".into()).unwrap();
+        inter.run("b = [0, 1; 1, 0]".into()).unwrap();
+        inter.run("m(x) = a * x * b * a".into()).unwrap();
+        inter.derive("m").unwrap();
+        assert_eq!(inter.funcs["m'"].1.to_string(), "a * b * a");
+        assert_eq!(
+            inter.run("m'(1) == a * b * a".into()).unwrap().to_string(),
+            "1"
+        );
+    }
+
+    #[test]
+    fn solve_roots() {
+        let mut inter = Interpreter::default();
+        let root = inter.run("solve(x^3 - 2*x - 5, x, 2)".into()).unwrap();
+        assert!((root.to_f64() - 2.0945514815423265).abs() < 1e-12);
+
+        inter.run("f(x) = cos(x) - x".into()).unwrap();
+        let root = inter.run("solve(f(t), t, 0, 1)".into()).unwrap();
+        assert!((root.to_f64() - 0.7390851332151607).abs() < 1e-12);
+
+        let root = inter.run("solve(abs(x) - 3, x, 1)".into()).unwrap();
+        assert!((root.to_f64().abs() - 3.).abs() < 1e-12);
+
+        assert!(inter.run("solve(x^2 + 1, x, 1)".into()).is_err());
+
+        // the roots either side of the guess are still found
+        let root = inter.run("solve(x^2 - 4, x, 0)".into()).unwrap();
+        assert!((root.to_f64().abs() - 2.).abs() < 1e-12);
+
+        // a sign change across a pole is not a root
+        assert!(inter.run("solve(1/x, x, 1)".into()).is_err());
+        assert!(inter.run("solve(1/x, x, -1, 1)".into()).is_err());
+    }
+
+    #[test]
+    fn integrals_and_series() {
+        let mut inter = Interpreter::default();
+        let area = inter.run("integrate(sin(x), x, 0, pi)".into()).unwrap();
+        assert!((area.to_f64() - 2.).abs() < 1e-9);
+        let area = inter.run("integrate(1/x, x, 1, e)".into()).unwrap();
+        assert!((area.to_f64() - 1.).abs() < 1e-9);
+
+        // integrands with jumps converge too
+        let area = inter
+            .run("integrate(if x < 1 then 0 else 1, x, 0, 2)".into())
+            .unwrap();
+        assert!((area.to_f64() - 1.).abs() < 1e-9);
+        let area = inter.run("integrate(floor(x), x, 0, 3)".into()).unwrap();
+        assert!((area.to_f64() - 3.).abs() < 1e-9);
+
+        assert_eq!(
+            inter.run("sum(k, k, 1, 100)".into()).unwrap(),
+            Value::from(5050)
+        );
+        assert_eq!(
+            inter.run("sum(1/2^k, k, 1, 3)".into()).unwrap().to_string(),
+            "0.875"
+        );
+        assert_eq!(
+            inter.run("prod(k, k, 1, 5)".into()).unwrap(),
+            Value::from(120)
+        );
+        assert!(inter.run("sum(k, k, 1, 2.5)".into()).is_err());
+
+        // terms combine like + and * so lists and units work
+        let cases = [
+            ("sum([k, 1], k, 1, 3)", "[6, 3]"),
+            ("prod([k, 2], k, 1, 3)", "[6, 8]"),
+            ("sum(k * 1 m, k, 1, 3)", "6 m"),
+            ("sum(k, k, 3, 1)", "0"),
+            ("prod(k, k, 3, 1)", "1"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(inter.run(input.into()).unwrap().to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn implicit_multiplication() {
+        let mut inter = Interpreter::default();
+        inter.run("x = 3".into()).unwrap();
+        let cases = [
+            ("2x", 6),
+            ("2x^2", 18),
+            ("2^3x", 24),
+            ("3(x + 1)", 12),
+            ("(x + 1)(x - 1)", 8),
+            ("2 abs(-x)", 6),
+            ("-2x", -6),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(inter.run(input.into()).unwrap(), Value::from(expected));
+        }
+        assert_eq!(inter.run("1/2x".into()).unwrap().to_string(), "1/6");
+
+        inter.run("a = 5".into()).unwrap();
+        assert_eq!(inter.run("a(2)".into()).unwrap(), Value::from(10));
+        inter.run("g(t) = a(t + 1)".into()).unwrap();
+        assert_eq!(inter.run("g'(1)".into()).unwrap(), Value::from(5));
+    }
+
+    #[test]
+    fn conditionals() {
+        let mut inter = Interpreter::default();
+        inter.run("f(x) = if x < 0 then -x else x".into()).unwrap();
+        assert_eq!(inter.run("f(-3)".into()).unwrap(), Value::from(3));
+        assert_eq!(inter.run("f(4)".into()).unwrap(), Value::from(4));
+        assert_eq!(inter.run("f'(-2)".into()).unwrap(), Value::from(-1));
+
+        let cases = [
+            ("1 < 2 and 2 <= 2", 1),
+            ("not 1 == 1 or 3 != 4", 1),
+            ("2 > 3 or 1 >= 2", 0),
+            ("1/2 == 0.5", 1),
+            ("1 km > 999 m", 1),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(inter.run(input.into()).unwrap(), Value::from(expected));
+        }
+
+        // only the branch that is taken is evaluated
+        assert_eq!(inter.run("0 and 1/0".into()).unwrap(), Value::from(0));
+        assert_eq!(inter.run("1 or 1/0".into()).unwrap(), Value::from(1));
+        assert_eq!(
+            inter.run("if 1 > 2 then 1/0 else 7".into()).unwrap(),
+            Value::from(7)
+        );
+        assert!(inter.run("i < 1".into()).is_err());
+    }
+
+    #[test]
+    fn recursion_limit() {
+        let mut inter = Interpreter::default();
+        inter
+            .run("f(n) = if n <= 1 then 1 else n * f(n - 1)".into())
+            .unwrap();
+        assert_eq!(inter.run("f(10)".into()).unwrap(), Value::from(3628800));
+        assert_eq!(inter.run("f'(2)".into()).unwrap(), Value::from(1));
+
+        assert!(inter.set_recursion_limit("20000").is_err());
+        assert!(inter.set_recursion_limit("-1").is_err());
+        assert_eq!(inter.recursion_limit, interpreter::DEFAULT_RECURSION_LIMIT);
+        inter.set_recursion_limit("20").unwrap();
+        let err = inter.run("f(100)".into()).unwrap_err().to_string();
+        assert!(err.starts_with("recursion limit of 20 exceeded"));
+        assert!(err.contains("\n    f\n"));
+        assert!(err.ends_with("... 12 more"));
+
+        // parameters are restored and the stack unwound after the error
+        assert!(inter.call_stack.is_empty());
+        assert!(inter.run("n".into()).is_err());
+        inter.run("n = 4".into()).unwrap();
+        assert_eq!(inter.run("f(n)".into()).unwrap(), Value::from(24));
+        assert_eq!(inter.run("n".into()).unwrap(), Value::from(4));
+    }
+
+    #[test]
+    fn lexical_scope() {
+        let mut inter = Interpreter::default();
+        // f can not see the parameter of the function calling it
+        inter.run("f(x) = x + y".into()).unwrap();
+        inter.run("g(y) = f(1)".into()).unwrap();
+        assert!(inter.run("g(5)".into()).is_err());
+        inter.run("y = 10".into()).unwrap();
+        assert_eq!(inter.run("g(5)".into()).unwrap(), Value::from(11));
+
+        // parameters shadow globals and built in constants without changing them
+        inter.run("x = 2".into()).unwrap();
+        inter.run("h(e) = 2e".into()).unwrap();
+        assert_eq!(inter.run("h(3)".into()).unwrap(), Value::from(6));
+        assert_eq!(inter.run("f(7)".into()).unwrap(), Value::from(17));
+        assert_eq!(inter.run("x".into()).unwrap(), Value::from(2));
+        assert!(inter.scope.is_none());
+
+        // expressions given to solve and sum still see the caller's parameters
+        inter.run("root(a) = solve(t^2 - a, t, 1)".into()).unwrap();
+        let root = inter.run("root(9)".into()).unwrap();
+        assert!((root.to_f64() - 3.).abs() < 1e-12);
+        inter.run("tri(n) = sum(k, k, 1, n)".into()).unwrap();
+        assert_eq!(inter.run("tri(4)".into()).unwrap(), Value::from(10));
+    }
+
+    #[test]
+    fn lambdas() {
+        let mut inter = Interpreter::default();
+        inter.run("sq = x -> x^2".into()).unwrap();
+        inter.run("add = (a, b) -> a + b".into()).unwrap();
+        inter.run("f(x) = x + 1".into()).unwrap();
+        inter.run("twice(g, x) = g(g(x))".into()).unwrap();
+        inter.run("scale(a) = x -> a * x".into()).unwrap();
+        inter.run("h = compose(sq, f)".into()).unwrap();
+        inter.run("triple = scale(3)".into()).unwrap();
+
+        let cases = [
+            ("sq(3)", 9),
+            ("fold(add, 0, 1, 2, 3, 4)", 10),
+            ("apply(add, 2, 3)", 5),
+            ("h(2)", 9),
+            ("twice(f, 1)", 3),
+            ("triple(4)", 12),
+            ("twice(y -> 3y, 2)", 18),
+            ("sum(k -> k^2, 1, 3)", 14),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(inter.run(input.into()).unwrap(), Value::from(expected));
+        }
+
+        let root = inter.run("solve(x -> x^2 - 2, 1)".into()).unwrap();
+        assert!((root.to_f64() - 2f64.sqrt()).abs() < 1e-12);
+        let area = inter.run("integrate(sq, 0, 3)".into()).unwrap();
+        assert!((area.to_f64() - 9.).abs() < 1e-9);
+
+        assert_eq!(inter.run("sq".into()).unwrap().to_string(), "x -> x^2");
+        assert!(inter.run("sq + 1".into()).is_err());
+        assert!(inter.run("sin(sq)".into()).is_err());
+        assert!(inter.run("add(1)".into()).is_err());
+
+        // functions passed by name keep it in errors, anonymous ones are lambda
+        let errors = [
+            ("apply(f, 1, 2)", "f takes 1 arguments but 2 were given"),
+            ("fold(sq, 0, [1])", "sq takes 1 arguments but 2 were given"),
+            ("map(add, [1])", "add takes 2 arguments but 1 were given"),
+            (
+                "apply(x -> x, 1, 2)",
+                "lambda takes 1 arguments but 2 were given",
+            ),
+        ];
+        for (input, expected) in errors {
+            let err = inter.run(input.into()).unwrap_err().to_string();
+            assert_eq!(err, expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn lists() {
+        let mut inter = Interpreter::default();
+        inter.run("xs = [1, 2, 3]".into()).unwrap();
+        let cases = [
+            ("xs * 2", "[2, 4, 6]"),
+            ("xs + [10, 20, 30]", "[11, 22, 33]"),
+            ("2^xs", "[2, 4, 8]"),
+            ("-xs", "[-1, -2, -3]"),
+            ("xs[0]", "1"),
+            ("xs[-1]", "3"),
+            ("xs[[0, 2]]", "[1, 3]"),
+            ("1..5", "[1, 2, 3, 4, 5]"),
+            ("[]", "[]"),
+            ("sum(1..100)", "5050"),
+            ("mean(xs)", "2"),
+            ("len(1..10)", "10"),
+            ("max(xs)", "3"),
+            ("min([4, -2, 7])", "-2"),
+            ("prod(1..5)", "120"),
+            ("map(x -> x^2, xs)", "[1, 4, 9]"),
+            ("fold((a, b) -> a * b, 1, 1..5)", "120"),
+            ("abs([-1, 2])", "[1, 2]"),
+            ("[1 km, 2 km] to m", "[1000 m, 2000 m]"),
+            ("xs == [1, 2, 3]", "1"),
+            ("sum([1 m, 2 m])", "3 m"),
+            ("mean([1 m, 2 m])", "1.5 m"),
+            ("sum([])", "0"),
+            ("prod([])", "1"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(inter.run(input.into()).unwrap().to_string(), expected);
+        }
+
+        assert!(inter.run("[1, 2] + [1, 2, 3]".into()).is_err());
+        assert!(inter.run("xs[3]".into()).is_err());
+        assert!(inter.run("xs < 2".into()).is_err());
+        assert!(inter.run("1..2.5".into()).is_err());
+    }
+
+    #[test]
+    fn matrices() {
+        let mut inter = Interpreter::default();
+        inter.run("m = [2, 1; 1, 3]".into()).unwrap();
+        let cases = [
+            ("m * m", "[5, 5; 5, 10]"),
+            ("det(m)", "5"),
+            ("inv(m)", "[0.6, -0.2; -0.2, 0.4]"),
+            ("m^-1 == inv(m)", "1"),
+            ("m^3", "[15, 20; 20, 35]"),
+            ("solve(m, [3, 5])", "[0.8, 1.4]"),
+            ("m * [1, 2]", "[4, 7]"),
+            ("m + 1", "[3, 2; 2, 4]"),
+            ("m * 2", "[4, 2; 2, 6]"),
+            ("-m", "[-2, -1; -1, -3]"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(inter.run(input.into()).unwrap().to_string(), expected);
+        }
+
+        let errors = [
+            "[1, 2; 3]",
+            "[1, 2; 3, 4] * [1, 2, 3; 4, 5, 6; 7, 8, 9]",
+            "inv([1, 2; 2, 4])",
+            "det([1, 2, 3; 4, 5, 6])",
+            "m + [1, 2, 3; 4, 5, 6]",
+            "1 / m",
+        ];
+        for input in errors {
+            assert!(inter.run(input.into()).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn statistics() {
+        let mut inter = Interpreter::default();
+        inter.run("xs = [2, 4, 4, 4, 5, 5, 7, 9]".into()).unwrap();
+        let cases = [
+            ("median(xs)", "4.5"),
+            ("var(xs)", "32/7"),
+            ("pstdev(xs)", "2"),
+            ("stdev([1, 2, 3])", "1"),
+            ("quantile(xs, 1/2) == median(xs)", "1"),
+            ("var([1 m, 2 m])", "0.5 m^2"),
+            ("pstdev([1 m, 3 m])", "1 m"),
+            ("corr([1 m, 2 m, 3 m], [2, 4, 6])", "1"),
+            ("binompdf(10, 0.5, 3)", "0.1171875"),
+            ("invnorm(0.5, 100, 15)", "100"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(inter.run(input.into()).unwrap().to_string(), expected);
+        }
+
+        let errors = [
+            "median(1)",
+            "corr([1 m, 1 m], [1, 2])",
+            "quantile(xs, 2)",
+            "invnorm(1.5)",
+            "tcdf(1)",
+        ];
+        for input in errors {
+            assert!(inter.run(input.into()).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn special_functions() {
+        let mut inter = Interpreter::default();
+        let cases = [
+            ("gamma(5)", "24"),
+            ("gamma([1, 2, 3])", "[1, 1, 2]"),
+            ("zeta(-2)", "0"),
+            ("lambertw(-1/e)", "-1"),
+            ("besselj(3, 0)", "0"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(inter.run(input.into()).unwrap().to_string(), expected);
+        }
+
+        inter.run("f(x) = erf(x)".into()).unwrap();
+        inter.run("g(x) = besselj(0, x)".into()).unwrap();
+        let derivatives = [
+            ("f'(0)", 2. / std::f64::consts::PI.sqrt()),
+            ("g'(1)", -0.440_050_585_744_933_5),
+        ];
+        for (input, expected) in derivatives {
+            let res = inter.run(input.into()).unwrap().to_f64();
+            assert!((res - expected).abs() < 1e-13, "{} = {}", input, res);
+        }
+
+        let errors = [
+            "gamma(-1)",
+            "zeta(1)",
+            "bessely(0, 0)",
+            "besselj(1/2, 1)",
+            "lambertw(-1)",
+            "erf(i)",
+            "beta(1)",
+        ];
+        for input in errors {
+            assert!(inter.run(input.into()).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn elementary_functions() {
+        let mut inter = Interpreter::default();
+        let cases = [
+            ("asin(1) == pi/2", "1"),
+            ("acos(1)", "0"),
+            ("atan(1) == pi/4", "1"),
+            ("cosh(0)", "1"),
+            ("exp(0)", "1"),
+            ("cbrt(-8/27)", "-2/3"),
+            ("floor(7/2)", "3"),
+            ("ceil(-7/2)", "-3"),
+            ("round(-5/2)", "-3"),
+            ("trunc(-3.7)", "-3"),
+            ("floor(3.7 m)", "3 m"),
+            ("sign(-4)", "-1"),
+            ("sign(3i)", "0 + 1i"),
+            ("sec(0)", "1"),
+            ("floor([1.5, 2.5])", "[1, 2]"),
+            ("map(sqrt, [1, 4, 9])", "[1, 2, 3]"),
+            ("sum(1..4)", "10"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(inter.run(input.into()).unwrap().to_string(), expected);
+        }
+
+        let approx = [
+            ("sinh(1)", 1f64.sinh()),
+            ("tanh(1)", 1f64.tanh()),
+            ("asinh(1)", 1f64.asinh()),
+            ("acosh(2)", 2f64.acosh()),
+            ("atanh(0.5)", 0.5f64.atanh()),
+            ("csc(1)", 1. / 1f64.sin()),
+            ("cot(1)", 1. / 1f64.tan()),
+        ];
+        for (input, expected) in approx {
+            let res = inter.run(input.into()).unwrap().to_f64();
+            assert!((res - expected).abs() < 1e-15, "{} = {}", input, res);
+        }
+        assert!(inter.run("acos(2)".into()).unwrap().is_complex());
+
+        inter.run("f(x) = exp(x^2)".into()).unwrap();
+        inter.run("g(x) = atan(x)".into()).unwrap();
+        inter.run("h(x) = floor(x)".into()).unwrap();
+        assert_eq!(inter.run("f'(0)".into()).unwrap().to_string(), "0");
+        assert_eq!(inter.run("g'(1)".into()).unwrap().to_string(), "0.5");
+
+        let errors = [
+            "sin(1, 2)",
+            "normpdf(1, 2)",
+            "floor(1 + i)",
+            "ln(2 m)",
+            "h'(1)",
+        ];
+        for input in errors {
+            assert!(inter.run(input.into()).is_err(), "{}", input);
+        }
+
+        assert_eq!(inter.completions("ta"), ["tan", "tanh"]);
+        inter.run("tau = 2 pi".into()).unwrap();
+        assert_eq!(inter.completions("ta"), ["tan", "tanh", "tau"]);
+        // tab completes only the name under the cursor
+        let completion = Completion {
+            names: inter.completions(""),
+        };
+        let history = DefaultHistory::new();
+        let context = rustyline::Context::new(&history);
+        let complete = |line: &str, pos| completion.complete(line, pos, &context).unwrap();
+        assert_eq!(
+            complete("2 * ta", 6),
+            (4, vec!["tan".into(), "tanh".into(), "tau".into()])
+        );
+        assert_eq!(complete("tau(sin_", 8), (4, vec![]));
+        assert_eq!(
+            complete("tanh + 1", 2),
+            (0, vec!["tan".into(), "tanh".into(), "tau".into()])
+        );
+        assert_eq!(complete("1 + ", 4), (4, vec![]));
+        assert!(builtins::BUILTINS
+            .iter()
+            .all(|builtin| builtin.usage.starts_with(builtin.name)));
+    }
+
+    #[test]
+    fn angle_modes() {
+        let mut inter = Interpreter::default();
+        assert_eq!(inter.run("sin(90deg)".into()).unwrap().to_string(), "1");
+        assert_eq!(
+            inter.run("90deg to rad".into()).unwrap().to_string(),
+            "1.5707963267948966 rad"
+        );
+
+        inter.set_angle_mode(AngleMode::Degrees);
+        let cases = [
+            ("sin(180)", "0"),
+            ("cos(-90)", "0"),
+            ("sin(450)", "1"),
+            ("asin(1)", "90"),
+            ("atan(1)", "45"),
+            ("sin(pi/2 * 1 rad)", "1"),
+            ("cos(100grad)", "0"),
+            ("sin([0, 90, 270])", "[0, 1, -1]"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(
+                inter.run(input.into()).unwrap().to_string(),
+                expected,
+                "{}",
+                input
+            );
+        }
+        assert!((inter.run("sin(30)".into()).unwrap().to_f64() - 0.5).abs() < 1e-15);
+        assert!(inter.run("tan(90)".into()).is_err());
+        assert!(inter.run("sin(2 m)".into()).is_err());
+
+        inter.run("f(x) = sin(x)".into()).unwrap();
+        let slope = inter.run("f'(0)".into()).unwrap().to_f64();
+        assert!((slope - std::f64::consts::PI / 180.).abs() < 1e-15);
+        inter.set_angle_mode(AngleMode::Gradians);
+        assert_eq!(inter.run("acos(0)".into()).unwrap().to_string(), "100");
+        let slope = inter.run("f'(0)".into()).unwrap().to_f64();
+        assert!((slope - std::f64::consts::PI / 200.).abs() < 1e-15);
+
+        let path = env::temp_dir().join("cl-calc-angle-modes.calc");
+        fs::write(&path, "!deg\nx = asin(1)\n!rad\nx + asin(1)").unwrap();
+        let mut inter = Interpreter::default();
+        let (_, res) = inter.run_file(path).unwrap();
+        assert!((res.to_f64() - (90. + std::f64::consts::FRAC_PI_2)).abs() < 1e-12);
+        assert_eq!(inter.angle_mode, AngleMode::Radians);
+    }
+
+    #[test]
+    fn programmer_mode() {
+        let mut inter = Interpreter::default();
+        let cases = [
+            ("0xff", "255"),
+            ("0b1010 + 0o17", "25"),
+            ("0xf0 | 0x0f", "255"),
+            ("12 & 10", "8"),
+            ("5 xor 3", "6"),
+            ("1 << 10", "1024"),
+            ("-16 >> 2", "-4"),
+            ("~0", "-1"),
+            ("x = 6", "6"),
+            ("x & 1 == 0", "1"),
+            ("1 + 1 << 2", "8"),
+            ("[1, 2, 3] << 1", "[2, 4, 6]"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(
+                inter.run(input.into()).unwrap().to_string(),
+                expected,
+                "{}",
+                input
+            );
+        }
+        assert!(inter.run("1.5 & 1".into()).is_err());
+        assert!(inter.run("1 << -1".into()).is_err());
+
+        inter.set_int_width("u8").unwrap();
+        assert_eq!(inter.run("255 + 1".into()).unwrap(), Value::from(0));
+        assert_eq!(inter.run("sum([255, 1])".into()).unwrap(), Value::from(0));
+        assert_eq!(
+            inter.run("prod(k, k, 1, 6)".into()).unwrap(),
+            Value::from(208)
+        );
+        assert_eq!(inter.run("~0".into()).unwrap(), Value::from(255));
+        let cases = [
+            ("[255, 1; 2, 3] + 1", "[0, 2; 3, 4]"),
+            ("[255, 256] + 1", "[0, 1]"),
+            ("255 m + 1 m", "0 m"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(inter.run(input.into()).unwrap().to_string(), expected);
+        }
+        inter.set_int_width("i8").unwrap();
+        assert_eq!(inter.run("127 + 1".into()).unwrap(), Value::from(-128));
+        assert!(inter.set_int_width("u7").is_err());
+        inter.set_int_width("off").unwrap();
+        assert_eq!(inter.run("127 + 1".into()).unwrap(), Value::from(128));
+
+        inter.set_output_base("16").unwrap();
+        let value = inter.run("[255, -16, 1/2]".into()).unwrap();
+        assert_eq!(inter.display(&value), "[0xff, -0x10, 0.5]");
+        let value = inter.run("[1, 2; 3, 10]".into()).unwrap();
+        assert_eq!(inter.display(&value), "[0x1, 0x2; 0x3, 0xa]");
+        inter.set_output_base("2").unwrap();
+        assert_eq!(inter.display(&Value::from(5)), "0b101");
+        assert!(inter.set_output_base("3").is_err());
+
+        // a saved session replays the modes along with the calculations
+        let mut inter = Interpreter::default();
+        for line in ["!base 16", "!width u8", "x = 250", "x + 10"] {
+            if inter.run_command(line).is_none() {
+                inter.run(line.into()).unwrap();
+            }
+        }
+        let path = env::temp_dir().join("cl-calc-programmer-mode.calc");
+        fs::write(&path, inter.executed_lines.join("\n")).unwrap();
+        let mut replayed = Interpreter::default();
+        let (_, res) = replayed.run_file(path).unwrap();
+        assert_eq!(replayed.display(&res), "0x4");
+        assert_eq!(replayed.executed_lines, inter.executed_lines);
+    }
+
+    #[test]
+    fn number_literals() {
+        let mut inter = Interpreter::default();
+        let cases = [
+            ("6.022e23", "602200000000000000000000"),
+            ("1.5E-3", "0.0015"),
+            ("2e+2", "200"),
+            ("1_000_000", "1000000"),
+            ("0xff_ff", "65535"),
+            (".5 + .25", "0.75"),
+            ("1..3", "[1, 2, 3]"),
+            ("2.5e3 m", "2500 m"),
+            ("2e - 1 == 2 e - 1", "1"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(
+                inter.run(input.into()).unwrap().to_string(),
+                expected,
+                "{}",
+                input
+            );
+        }
+        // large exponents stay exact integers rather than overflowing to a float
+        let big = inter.run("1e400".into()).unwrap();
+        assert_eq!(big.to_string(), format!("1{}", "0".repeat(400)));
+        assert_eq!(inter.run("1e400 == 10^400".into()).unwrap(), Value::from(1));
+
+        let errors = [
+            ("1.", "expected a digit after the decimal point\n1.\n  ^"),
+            ("1__0", "a digit separator must be between digits\n1__0\n ^"),
+            (
+                "2 + 1_",
+                "a digit separator must be between digits\n2 + 1_\n     ^",
+            ),
+            ("0xfg", "expected a hex digit\n0xfg\n   ^"),
+            (
+                "1.5.2",
+                "a number can only have one decimal point\n1.5.2\n   ^",
+            ),
+            ("3 $ 4", "Invalid character: $\n3 $ 4\n  ^"),
+        ];
+        for (input, expected) in errors {
+            let err = inter.run(input.into()).unwrap_err();
+            assert_eq!(err.to_string(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn identifiers_and_symbols() {
+        let mut inter = Interpreter::default();
+        let cases = [
+            ("x1 = 3", "3"),
+            ("v_0 = 2", "2"),
+            ("rate2 = x1 * v_0", "6"),
+            ("2x1", "6"),
+            ("f(x1, x2) = x1 - x2", "0"),
+            ("f(5, 2)", "3"),
+            ("log2(8)", "3"),
+            ("atan2(1, 1) == pi/4", "1"),
+            ("π == pi", "1"),
+            ("√16 + √(9)", "7"),
+            ("6 × 7 ÷ 2", "21"),
+            ("3 − 1", "2"),
+            ("3 s", "3 s"),
+            ("s_1 = 4", "4"),
+            ("3 s_1", "12"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(
+                inter.run(input.into()).unwrap().to_string(),
+                expected,
+                "{}",
+                input
+            );
+        }
+
+        let errors = [
+            ("1 + * 2", "Invalid syntax, expected Number\n1 + * 2\n    ^"),
+            ("π × ) 2", "Invalid syntax, expected Number\nπ × ) 2\n    ^"),
+            ("(1 + 2", "Invalid syntax, expected RParen\n(1 + 2\n      ^"),
+        ];
+        for (input, expected) in errors {
+            let err = inter.run(input.into()).unwrap_err();
+            assert_eq!(err.to_string(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn comments_and_statements() {
+        let mut inter = Interpreter::default();
+        assert_eq!(
+            inter.run("a = 1; b = 2; a + b".into()).unwrap(),
+            Value::from(3)
+        );
+        assert_eq!(
+            inter.run("ans * 2; ans + 1 # comment".into()).unwrap(),
+            Value::from(7)
+        );
+        assert_eq!(
+            inter.run("# only a comment".into()).unwrap(),
+            Value::from(7)
+        );
+        assert_eq!(
+            inter.run("m = [1, 2; 3, 4]; det(m)".into()).unwrap(),
+            Value::from(-2)
+        );
+        assert!(inter.run("1; ]".into()).is_err());
+        assert!(inter.run("ans + 1; 1 / 0".into()).is_err());
+        assert_eq!(inter.run("ans".into()).unwrap(), Value::from(-2));
+        let saved = inter.executed_lines.len();
+        assert!(inter.run("a = 5; g(x) = x; 1 / 0".into()).is_err());
+        assert_eq!(inter.run("a".into()).unwrap(), Value::from(1));
+        assert!(!inter.funcs.contains_key("g"));
+        assert_eq!(inter.executed_lines.len(), saved + 1);
+
+        let path = env::temp_dir().join("cl-calc-comments.calc");
+        let worksheet =
+            "# circle\n\nr = 2 # radius\n!deg # angles in degrees\n!sin(90); c = 2 r\n\nc + 1\n";
+        fs::write(&path, worksheet).unwrap();
+        let mut inter = Interpreter::default();
+        let (out, res) = inter.run_file(path).unwrap();
+        assert_eq!(out, [Value::from(4)]);
+        assert_eq!(res, Value::from(5));
+        assert_eq!(inter.angle_mode, AngleMode::Degrees);
+    }
+
+    #[test]
+    fn names_shadow_units() {
+        let mut inter = Interpreter::default();
+        let cases = [
+            ("g(t) = 3t", "0"),
+            ("g(2)", "6"),
+            ("g'(1)", "3"),
+            ("v(m) = 2m", "0"),
+            ("v(5)", "10"),
+            ("k(s) = 2s + s", "0"),
+            ("k(2)", "6"),
+            ("w(g) = 2g^2", "0"),
+            ("w(3)", "18"),
+            ("h(x) = x", "0"),
+            ("2 h(3)", "6"),
+            ("2 min([3, 4])", "6"),
+            ("2 s", "2 s"),
+            ("2 g", "2 g"),
+            ("p(m, s) = 2m/s", "0"),
+            ("p(6, 3)", "4"),
+            ("f(h) = 2h*h", "0"),
+            ("f(3)", "18"),
+            ("a(s) = 5 m/s^2", "0"),
+            ("a(2)", "1.25 m"),
+            ("d(t) = 3 m/s*t", "0"),
+            ("d'(1)", "3 m/s"),
+            ("3 km/h", "3 km/h"),
+            ("m = 4", "4"),
+            ("2m", "8"),
+            // the units that are not names stay units
+            ("3 m/s", "12 s^-1"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(
+                inter.run(input.into()).unwrap().to_string(),
+                expected,
+                "{}",
+                input
+            );
+        }
     }
 }
@@ -0,0 +1,362 @@
+use std::fmt::Display;
+
+use crate::{ast::fmt_list, value::Value};
+
+// floats this close to zero are treated as zero when picking pivots
+const PIVOT_TOLERANCE: f64 = 1e-12;
+
+// rectangular, with at least one row and one column
+#[derive(Debug, Clone, PartialEq)]
+pub struct Matrix {
+    rows: Vec<Vec<Value>>,
+}
+
+fn is_zero(value: &Value) -> bool {
+    match value {
+        Value::Float(float) => float.abs() < PIVOT_TOLERANCE,
+        Value::Complex(complex) => complex.norm() < PIVOT_TOLERANCE,
+        value => *value == Value::default(),
+    }
+}
+
+impl Matrix {
+    pub fn new(rows: Vec<Vec<Value>>) -> anyhow::Result<Self> {
+        let width = rows.first().map_or(0, Vec::len);
+        if width == 0 {
+            anyhow::bail!("matrices need at least one element");
+        }
+        for row in &rows {
+            if row.len() != width {
+                anyhow::bail!(
+                    "matrix rows have different lengths: {} and {}",
+                    width,
+                    row.len()
+                );
+            }
+            if let Some(item) = row.iter().find(|item| !item.is_number()) {
+                anyhow::bail!("matrix elements must be numbers but got {}", item);
+            }
+        }
+        Ok(Self { rows })
+    }
+
+    // a list used as a vector is a column
+    pub fn column(items: Vec<Value>) -> anyhow::Result<Self> {
+        Self::new(items.into_iter().map(|item| vec![item]).collect())
+    }
+
+    pub fn row(items: Vec<Value>) -> anyhow::Result<Self> {
+        Self::new(vec![items])
+    }
+
+    fn identity(size: usize) -> Self {
+        let rows = (0..size)
+            .map(|i| (0..size).map(|j| Value::from((i == j) as i64)).collect())
+            .collect();
+        Self { rows }
+    }
+
+    pub fn rows(&self) -> &[Vec<Value>] {
+        &self.rows
+    }
+
+    pub fn height(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn width(&self) -> usize {
+        self.rows[0].len()
+    }
+
+    fn shape(&self) -> String {
+        format!("{}x{}", self.height(), self.width())
+    }
+
+    pub fn into_items(self) -> Vec<Value> {
+        self.rows.into_iter().flatten().collect()
+    }
+
+    pub fn map(self, mut f: impl FnMut(Value) -> Value) -> Self {
+        let rows = self
+            .rows
+            .into_iter()
+            .map(|row| row.into_iter().map(&mut f).collect())
+            .collect();
+        Self { rows }
+    }
+
+    pub fn try_map(
+        self,
+        mut f: impl FnMut(Value) -> anyhow::Result<Value>,
+    ) -> anyhow::Result<Self> {
+        let rows = self
+            .rows
+            .into_iter()
+            .map(|row| row.into_iter().map(&mut f).collect())
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self { rows })
+    }
+
+    // combines matching elements of two matrices of the same shape
+    pub fn zip_with(
+        self,
+        rhs: Matrix,
+        mut f: impl FnMut(Value, Value) -> anyhow::Result<Value>,
+    ) -> anyhow::Result<Self> {
+        if self.height() != rhs.height() || self.width() != rhs.width() {
+            anyhow::bail!(
+                "matrices have different shapes: {} and {}",
+                self.shape(),
+                rhs.shape()
+            );
+        }
+        let rows = self
+            .rows
+            .into_iter()
+            .zip(rhs.rows)
+            .map(|(lhs, rhs)| lhs.into_iter().zip(rhs).map(|(a, b)| f(a, b)).collect())
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self { rows })
+    }
+
+    pub fn transpose(&self) -> Self {
+        let rows = (0..self.width())
+            .map(|j| self.rows.iter().map(|row| row[j].clone()).collect())
+            .collect();
+        Self { rows }
+    }
+
+    pub fn mul(&self, rhs: &Matrix) -> anyhow::Result<Self> {
+        if self.width() != rhs.height() {
+            anyhow::bail!(
+                "can not multiply a {} matrix by a {} matrix",
+                self.shape(),
+                rhs.shape()
+            );
+        }
+
+        let mut rows = Vec::with_capacity(self.height());
+        for row in &self.rows {
+            let mut out = Vec::with_capacity(rhs.width());
+            for j in 0..rhs.width() {
+                let mut acc = Value::default();
+                for (k, item) in row.iter().enumerate() {
+                    acc = acc.checked_add(item.clone() * rhs.rows[k][j].clone())?;
+                }
+                out.push(acc);
+            }
+            rows.push(out);
+        }
+        Ok(Self { rows })
+    }
+
+    // negative powers raise the inverse
+    pub fn pow(&self, exp: i64) -> anyhow::Result<Self> {
+        self.expect_square("raise")?;
+        let mut base = if exp < 0 { self.inv()? } else { self.clone() };
+        let mut exp = exp.unsigned_abs();
+        let mut acc = Self::identity(self.height());
+        while exp > 0 {
+            if exp & 1 == 1 {
+                acc = acc.mul(&base)?;
+            }
+            base = base.mul(&base)?;
+            exp >>= 1;
+        }
+        Ok(acc)
+    }
+
+    fn expect_square(&self, action: &str) -> anyhow::Result<()> {
+        if self.height() != self.width() {
+            anyhow::bail!(
+                "can only {} square matrices but got a {} matrix",
+                action,
+                self.shape()
+            );
+        }
+        Ok(())
+    }
+
+    pub fn det(&self) -> anyhow::Result<Value> {
+        self.expect_square("take the determinant of")?;
+        let mut rows = self.rows.clone();
+        let (pivots, swaps) = row_echelon(&mut rows)?;
+        if pivots < self.height() {
+            return Ok(Value::default());
+        }
+
+        let mut det = Value::from(if swaps % 2 == 0 { 1 } else { -1 });
+        for (i, row) in rows.iter().enumerate() {
+            det = det * row[i].clone();
+        }
+        Ok(det)
+    }
+
+    pub fn rank(&self) -> anyhow::Result<usize> {
+        let mut rows = self.rows.clone();
+        Ok(row_echelon(&mut rows)?.0)
+    }
+
+    pub fn inv(&self) -> anyhow::Result<Self> {
+        self.expect_square("invert")?;
+        self.solve(&Self::identity(self.height()))
+    }
+
+    // solves self * x = rhs by Gauss-Jordan elimination
+    pub fn solve(&self, rhs: &Matrix) -> anyhow::Result<Self> {
+        self.expect_square("solve with")?;
+        if rhs.height() != self.height() {
+            anyhow::bail!(
+                "can not solve a {} system with a {} right hand side",
+                self.shape(),
+                rhs.shape()
+            );
+        }
+
+        let size = self.height();
+        let mut rows: Vec<Vec<Value>> = self
+            .rows
+            .iter()
+            .zip(&rhs.rows)
+            .map(|(lhs, rhs)| lhs.iter().chain(rhs).cloned().collect())
+            .collect();
+
+        for col in 0..size {
+            let Some(pivot) = pivot_row(&rows, col, col) else {
+                anyhow::bail!("matrix is singular");
+            };
+            rows.swap(col, pivot);
+
+            let pivot = rows[col][col].clone();
+            rows[col] = rows[col]
+                .iter()
+                .map(|item| item.clone().checked_div(pivot.clone()))
+                .collect::<anyhow::Result<_>>()?;
+            for i in (0..size).filter(|&i| i != col) {
+                let factor = rows[i][col].clone();
+                rows[i] = subtract_scaled(&rows[i], &rows[col], factor)?;
+            }
+        }
+
+        let rows = rows.into_iter().map(|row| row[size..].to_vec()).collect();
+        Ok(Self { rows })
+    }
+}
+
+// the row at or below start with the largest entry in col, skipping zeros
+fn pivot_row(rows: &[Vec<Value>], col: usize, start: usize) -> Option<usize> {
+    (start..rows.len())
+        .filter(|&i| !is_zero(&rows[i][col]))
+        .max_by(|&a, &b| {
+            let (a, b) = (rows[a][col].clone().abs(), rows[b][col].clone().abs());
+            a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+        })
+}
+
+// row - factor * pivot
+fn subtract_scaled(row: &[Value], pivot: &[Value], factor: Value) -> anyhow::Result<Vec<Value>> {
+    row.iter()
+        .zip(pivot)
+        .map(|(item, pivot)| item.clone().checked_sub(factor.clone() * pivot.clone()))
+        .collect()
+}
+
+// reduces rows to row echelon form, returns the number of pivots and row swaps
+fn row_echelon(rows: &mut [Vec<Value>]) -> anyhow::Result<(usize, usize)> {
+    let width = rows.first().map_or(0, Vec::len);
+    let (mut pivots, mut swaps) = (0, 0);
+    for col in 0..width {
+        let Some(pivot) = pivot_row(rows, col, pivots) else {
+            continue;
+        };
+        if pivot != pivots {
+            rows.swap(pivot, pivots);
+            swaps += 1;
+        }
+
+        for i in pivots + 1..rows.len() {
+            let factor = rows[i][col]
+                .clone()
+                .checked_div(rows[pivots][col].clone())?;
+            rows[i] = subtract_scaled(&rows[i], &rows[pivots], factor)?;
+        }
+        pivots += 1;
+    }
+    Ok((pivots, swaps))
+}
+
+impl Display for Matrix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[")?;
+        for (i, row) in self.rows.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            fmt_list(f, row)?;
+        }
+        write!(f, "]")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matrix(rows: &[&[i64]]) -> Matrix {
+        let rows = rows
+            .iter()
+            .map(|row| row.iter().map(|&item| Value::from(item)).collect())
+            .collect();
+        Matrix::new(rows).unwrap()
+    }
+
+    #[test]
+    fn shapes() {
+        assert!(Matrix::new(vec![]).is_err());
+        assert!(Matrix::new(vec![
+            vec![Value::from(1), Value::from(2)],
+            vec![Value::from(3)]
+        ])
+        .is_err());
+        let m = matrix(&[&[1, 2, 3], &[4, 5, 6]]);
+        assert_eq!(m.transpose(), matrix(&[&[1, 4], &[2, 5], &[3, 6]]));
+        assert_eq!(
+            Matrix::column(vec![Value::from(1), Value::from(2)]).unwrap(),
+            matrix(&[&[1], &[2]])
+        );
+        assert!(m.mul(&m).is_err());
+        assert!(m.det().is_err());
+        assert!(m.inv().is_err());
+    }
+
+    #[test]
+    fn arithmetic() {
+        let m = matrix(&[&[2, 1], &[1, 3]]);
+        assert_eq!(m.mul(&m).unwrap(), matrix(&[&[5, 5], &[5, 10]]));
+        assert_eq!(m.pow(3).unwrap(), matrix(&[&[15, 20], &[20, 35]]));
+        assert_eq!(m.pow(-1).unwrap(), m.inv().unwrap());
+        assert_eq!(m.pow(0).unwrap(), Matrix::identity(2));
+        assert_eq!(m.inv().unwrap().to_string(), "[0.6, -0.2; -0.2, 0.4]");
+        assert_eq!(m.inv().unwrap().mul(&m).unwrap(), Matrix::identity(2));
+        let rhs = Matrix::column(vec![Value::from(3), Value::from(5)]).unwrap();
+        assert_eq!(m.solve(&rhs).unwrap().to_string(), "[0.8; 1.4]");
+    }
+
+    #[test]
+    fn elimination() {
+        assert_eq!(matrix(&[&[2, 1], &[1, 3]]).det().unwrap(), Value::from(5));
+        // a row swap flips the sign
+        assert_eq!(matrix(&[&[0, 1], &[1, 0]]).det().unwrap(), Value::from(-1));
+        assert_eq!(
+            matrix(&[&[1, 2, 3], &[4, 5, 6], &[7, 8, 10]])
+                .det()
+                .unwrap(),
+            Value::from(-3)
+        );
+        let singular = matrix(&[&[1, 2], &[2, 4]]);
+        assert_eq!(singular.det().unwrap(), Value::default());
+        assert_eq!(singular.rank().unwrap(), 1);
+        assert!(singular.inv().is_err());
+        assert_eq!(matrix(&[&[1, 2, 3], &[4, 5, 6]]).rank().unwrap(), 2);
+    }
+}
@@ -0,0 +1,358 @@
+use num_traits::ToPrimitive;
+
+use crate::{
+    ast::{Node, Operator},
+    interpreter::{self, Interpreter},
+    matrix::Matrix,
+    simplify::simplify,
+    value::Value,
+};
+
+const MAX_ITERATIONS: usize = 100;
+const TOLERANCE: f64 = 1e-12;
+// how many times the search interval around a guess is doubled looking for a sign change
+const MAX_BRACKET_STEPS: usize = 60;
+// a root must bring expr this close to zero, relative to its size at the guess or bounds
+const RESIDUAL_TOLERANCE: f64 = 1e-6;
+const INTEGRATION_TOLERANCE: f64 = 1e-10;
+const MAX_INTEGRATION_DEPTH: usize = 50;
+// sum and prod refuse ranges longer than this
+const MAX_SERIES_TERMS: u64 = 10_000_000;
+
+impl Interpreter {
+    // evaluates node as a real function of var
+    fn eval_real(&mut self, node: &Node, var: &str, x: f64) -> anyhow::Result<f64> {
+        let res = self.eval_with(&[var.to_owned()], vec![Value::Float(x)], node.clone())?;
+        if res.is_complex() || res.is_quantity() {
+            anyhow::bail!("expected a real number but got {}", res);
+        }
+        Ok(res.to_f64())
+    }
+
+    fn eval_bound(&mut self, node: &Node) -> anyhow::Result<f64> {
+        let res = self.step(node.clone())?;
+        if res.is_complex() || res.is_quantity() {
+            anyhow::bail!("expected a real number but got {}", res);
+        }
+        Ok(res.to_f64())
+    }
+
+    // `solve(x -> x^2 - 2, 1)` or `solve(f, 1)` is short for `solve(f(x), x, 1)`
+    fn function_args(&self, args: &[Node]) -> Vec<Node> {
+        let (expr, var) = match args.first() {
+            Some(Node::Lambda(vars, body)) if vars.len() == 1 => {
+                (body.as_ref().clone(), vars[0].clone())
+            }
+            Some(Node::Const(name)) => match self.lookup_function(name) {
+                Some(closure) if closure.vars.len() == 1 => {
+                    let var = closure.vars[0].clone();
+                    (
+                        Node::Function(name.clone(), vec![Node::Const(var.clone())]),
+                        var,
+                    )
+                }
+                _ => return args.to_vec(),
+            },
+            _ => return args.to_vec(),
+        };
+
+        let mut rewritten = vec![expr, Node::Const(var)];
+        rewritten.extend_from_slice(&args[1..]);
+        rewritten
+    }
+
+    // solve(expr, var, guess) or solve(expr, var, a, b) finds a root of expr
+    pub fn solve(&mut self, args: &[Node]) -> anyhow::Result<Value> {
+        let args = &self.function_args(args)[..];
+        if let [a, b] = args {
+            return self.solve_linear(a, b);
+        }
+        let (expr, var, bounds) = match args {
+            [expr, Node::Const(var), bounds @ ..] if matches!(bounds.len(), 1 | 2) => {
+                (expr, var, bounds)
+            }
+            _ => anyhow::bail!("solve takes an expression, a variable and a guess or two bounds"),
+        };
+
+        let bounds = bounds
+            .iter()
+            .map(|bound| self.eval_bound(bound))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let root = match bounds[..] {
+            [a, b] => self.brent(expr, var, a, b)?,
+            [guess] => match self.newton(expr, var, guess) {
+                Some(root) => root,
+                None => {
+                    let (a, b) = self.bracket(expr, var, guess)?;
+                    self.brent(expr, var, a, b)?
+                }
+            },
+            _ => unreachable!(),
+        };
+
+        // a sign change across a pole like 1/x at 0 converges without being a root
+        let scale = bounds
+            .iter()
+            .filter_map(|&x| self.eval_real(expr, var, x).ok())
+            .fold(1f64, |scale, fx| scale.max(fx.abs()));
+        let residual = self.eval_real(expr, var, root)?;
+        if residual.is_nan() || residual.abs() > RESIDUAL_TOLERANCE * scale {
+            anyhow::bail!(
+                "solve found a pole or jump at {} = {} rather than a root",
+                var,
+                root
+            );
+        }
+        Ok(Value::Float(root))
+    }
+
+    // solve(A, b) solves A x = b for a square matrix A and a vector or matrix b
+    fn solve_linear(&mut self, a: &Node, b: &Node) -> anyhow::Result<Value> {
+        let Value::Matrix(a) = self.step(a.clone())? else {
+            anyhow::bail!("solve takes a matrix and a vector, or an expression and a variable");
+        };
+        Ok(match self.step(b.clone())? {
+            Value::List(items) => Value::List(a.solve(&Matrix::column(items)?)?.into_items()),
+            Value::Matrix(b) => Value::Matrix(a.solve(&b)?),
+            b => anyhow::bail!("expected a vector but got {}", b),
+        })
+    }
+
+    // returns None when newton's method fails so a bracketing method can take over
+    fn newton(&mut self, expr: &Node, var: &str, guess: f64) -> Option<f64> {
+        let derivative = self.derivative(expr, var).ok().map(simplify);
+
+        let mut x = guess;
+        for _ in 0..MAX_ITERATIONS {
+            let fx = self.eval_real(expr, var, x).ok()?;
+            if fx == 0. {
+                return Some(x);
+            }
+
+            let dfx = match &derivative {
+                Some(derivative) => self.eval_real(derivative, var, x).ok()?,
+                None => {
+                    let h = 1e-7 * x.abs().max(1.);
+                    let forward = self.eval_real(expr, var, x + h).ok()?;
+                    let backward = self.eval_real(expr, var, x - h).ok()?;
+                    (forward - backward) / (2. * h)
+                }
+            };
+
+            let dx = fx / dfx;
+            if !dx.is_finite() {
+                return None;
+            }
+            x -= dx;
+
+            if dx.abs() <= TOLERANCE * x.abs().max(1.) {
+                return Some(x);
+            }
+        }
+        None
+    }
+
+    // widens an interval around guess until expr changes sign
+    fn bracket(&mut self, expr: &Node, var: &str, guess: f64) -> anyhow::Result<(f64, f64)> {
+        let fg = self.eval_real(expr, var, guess).ok();
+        let mut step = 0.1 * guess.abs().max(1.);
+        for _ in 0..MAX_BRACKET_STEPS {
+            let (a, b) = (guess - step, guess + step);
+            let fa = self.eval_real(expr, var, a).ok();
+            let fb = self.eval_real(expr, var, b).ok();
+            // roots on both sides like x^2 - 4 around 0 leave the ends with the same sign
+            let intervals = [
+                ((a, fa), (guess, fg)),
+                ((guess, fg), (b, fb)),
+                ((a, fa), (b, fb)),
+            ];
+            for ((lo, flo), (hi, fhi)) in intervals {
+                if let (Some(flo), Some(fhi)) = (flo, fhi) {
+                    if flo * fhi <= 0. {
+                        return Ok((lo, hi));
+                    }
+                }
+            }
+            step *= 2.;
+        }
+        anyhow::bail!("solve did not converge near {}", guess)
+    }
+
+    // Brent's method, mixes bisection, secant and inverse quadratic interpolation
+    fn brent(&mut self, expr: &Node, var: &str, a: f64, b: f64) -> anyhow::Result<f64> {
+        let (mut a, mut b) = (a, b);
+        let mut fa = self.eval_real(expr, var, a)?;
+        let mut fb = self.eval_real(expr, var, b)?;
+        if fa * fb > 0. {
+            anyhow::bail!("solve needs a sign change between {} and {}", a, b);
+        }
+        if fa.abs() < fb.abs() {
+            std::mem::swap(&mut a, &mut b);
+            std::mem::swap(&mut fa, &mut fb);
+        }
+
+        let (mut c, mut fc) = (a, fa);
+        let mut d = c;
+        let mut bisected = true;
+        for _ in 0..MAX_ITERATIONS {
+            if fb == 0. || (b - a).abs() <= TOLERANCE * b.abs().max(1.) {
+                return Ok(b);
+            }
+
+            let mut s = if fa != fc && fb != fc {
+                a * fb * fc / ((fa - fb) * (fa - fc))
+                    + b * fa * fc / ((fb - fa) * (fb - fc))
+                    + c * fa * fb / ((fc - fa) * (fc - fb))
+            } else {
+                b - fb * (b - a) / (fb - fa)
+            };
+
+            let quarter = (3. * a + b) / 4.;
+            let outside = !((quarter < s && s < b) || (b < s && s < quarter));
+            let slow = if bisected {
+                (s - b).abs() >= (b - c).abs() / 2. || (b - c).abs() < TOLERANCE
+            } else {
+                (s - b).abs() >= (c - d).abs() / 2. || (c - d).abs() < TOLERANCE
+            };
+            bisected = outside || slow;
+            if bisected {
+                s = (a + b) / 2.;
+            }
+
+            let fs = self.eval_real(expr, var, s)?;
+            d = c;
+            c = b;
+            fc = fb;
+            if fa * fs < 0. {
+                b = s;
+                fb = fs;
+            } else {
+                a = s;
+                fa = fs;
+            }
+
+            if fa.abs() < fb.abs() {
+                std::mem::swap(&mut a, &mut b);
+                std::mem::swap(&mut fa, &mut fb);
+            }
+        }
+        anyhow::bail!("solve did not converge between {} and {}", a, b)
+    }
+
+    // integrate(expr, var, a, b) with adaptive Simpson's rule
+    pub fn integrate(&mut self, args: &[Node]) -> anyhow::Result<Value> {
+        let args = &self.function_args(args)[..];
+        let [expr, Node::Const(var), a, b] = args else {
+            anyhow::bail!("integrate takes an expression, a variable and two bounds");
+        };
+        let (a, b) = (self.eval_bound(a)?, self.eval_bound(b)?);
+
+        let fa = self.eval_integrand(expr, var, a)?;
+        let fb = self.eval_integrand(expr, var, b)?;
+        let fm = self.eval_integrand(expr, var, (a + b) / 2.)?;
+
+        let res = self.simpson(
+            expr,
+            var,
+            (a, b),
+            (fa, fm, fb),
+            INTEGRATION_TOLERANCE,
+            MAX_INTEGRATION_DEPTH,
+        )?;
+        Ok(Value::Float(res))
+    }
+
+    fn eval_integrand(&mut self, expr: &Node, var: &str, x: f64) -> anyhow::Result<f64> {
+        let fx = self.eval_real(expr, var, x)?;
+        if !fx.is_finite() {
+            anyhow::bail!("integrand is not finite at {} = {}", var, x);
+        }
+        Ok(fx)
+    }
+
+    // splits the interval until the two halves agree with the whole to within tolerance
+    fn simpson(
+        &mut self,
+        expr: &Node,
+        var: &str,
+        (a, b): (f64, f64),
+        (fa, fm, fb): (f64, f64, f64),
+        tolerance: f64,
+        depth: usize,
+    ) -> anyhow::Result<f64> {
+        let whole = (b - a) / 6. * (fa + 4. * fm + fb);
+        let m = (a + b) / 2.;
+        let (left_mid, right_mid) = ((a + m) / 2., (m + b) / 2.);
+        let flm = self.eval_integrand(expr, var, left_mid)?;
+        let frm = self.eval_integrand(expr, var, right_mid)?;
+        let left = (m - a) / 6. * (fa + 4. * flm + fm);
+        let right = (b - m) / 6. * (fm + 4. * frm + fb);
+        let delta = left + right - whole;
+
+        // delta / 15 estimates the error of the combined halves
+        // the tolerance halves every level and falls below what f64 resolves, so at the last
+        // level a jump like `if x < 1 then 0 else 1` is accepted within the overall tolerance
+        let bottom = depth == 0 && delta.abs() <= 15. * INTEGRATION_TOLERANCE;
+        if delta.abs() <= 15. * tolerance || bottom {
+            return Ok(left + right + delta / 15.);
+        }
+        if depth == 0 {
+            anyhow::bail!(
+                "integrate did not converge between {} and {}, error estimate {}",
+                a,
+                b,
+                delta.abs() / 15.
+            );
+        }
+
+        let left = self.simpson(expr, var, (a, m), (fa, flm, fm), tolerance / 2., depth - 1)?;
+        let right = self.simpson(expr, var, (m, b), (fm, frm, fb), tolerance / 2., depth - 1)?;
+        Ok(left + right)
+    }
+
+    // sum(expr, var, from, to) and prod(expr, var, from, to) over an integer range
+    pub fn series(&mut self, func: &str, args: &[Node]) -> anyhow::Result<Value> {
+        let args = &self.function_args(args)[..];
+        let [expr, Node::Const(var), from, to] = args else {
+            anyhow::bail!("{} takes an expression, a variable and two bounds", func);
+        };
+
+        let bound = |value: Value| match value {
+            Value::Int(int) => Ok(int),
+            value => Err(anyhow::anyhow!(
+                "{} bounds must be integers but got {}",
+                func,
+                value
+            )),
+        };
+        let from = bound(self.step(from.clone())?)?;
+        let to = bound(self.step(to.clone())?)?;
+
+        if (&to - &from)
+            .to_u64()
+            .is_some_and(|len| len >= MAX_SERIES_TERMS)
+        {
+            anyhow::bail!("{} range is too long", func);
+        }
+
+        let vars = [var.clone()];
+        let op = if func == "sum" {
+            Operator::Plus
+        } else {
+            Operator::Mult
+        };
+        // starts from the first term so lists and units add up, an empty range is 0 or 1
+        let mut acc: Option<Value> = None;
+        let mut i = from;
+        while i <= to {
+            let term = self.eval_with(&vars, vec![Value::Int(i.clone())], expr.clone())?;
+            acc = Some(match acc {
+                Some(acc) => interpreter::binary(op.clone(), acc, term)?,
+                None => term,
+            });
+            i += 1;
+        }
+        Ok(acc.unwrap_or_else(|| Value::from(if func == "sum" { 0 } else { 1 })))
+    }
+}
@@ -1,10 +1,108 @@
-
 use crate::{
-    ast::Node,
+    ast::{Node, Operator, UnaryOperator},
+    bits,
     lexer::Lexer,
     token::{Token, TokenType},
+    unit::Unit,
+    value::Value,
 };
 
+#[derive(PartialEq)]
+enum Associativity {
+    Left,
+    Right,
+}
+
+struct InfixOperator {
+    token: TokenType,
+    op: Operator,
+    precedence: u8,
+    associativity: Associativity,
+}
+
+impl InfixOperator {
+    const fn new(
+        token: TokenType,
+        op: Operator,
+        precedence: u8,
+        associativity: Associativity,
+    ) -> Self {
+        Self {
+            token,
+            op,
+            precedence,
+            associativity,
+        }
+    }
+
+    // (left, right) binding powers, a right associative operator binds its right side looser
+    fn binding_power(&self) -> (u8, u8) {
+        let left = self.precedence * 2;
+        match self.associativity {
+            Associativity::Left => (left, left + 1),
+            Associativity::Right => (left, left - 1),
+        }
+    }
+}
+
+// new binary operators only need an entry here, higher precedence binds tighter
+const INFIX_OPERATORS: &[InfixOperator] = &[
+    InfixOperator::new(TokenType::Or, Operator::Or, 1, Associativity::Left),
+    InfixOperator::new(TokenType::And, Operator::And, 2, Associativity::Left),
+    InfixOperator::new(TokenType::Less, Operator::Less, 3, Associativity::Left),
+    InfixOperator::new(TokenType::LessEq, Operator::LessEq, 3, Associativity::Left),
+    InfixOperator::new(TokenType::Equal, Operator::Equal, 3, Associativity::Left),
+    InfixOperator::new(
+        TokenType::NotEqual,
+        Operator::NotEqual,
+        3,
+        Associativity::Left,
+    ),
+    InfixOperator::new(
+        TokenType::Greater,
+        Operator::Greater,
+        3,
+        Associativity::Left,
+    ),
+    InfixOperator::new(
+        TokenType::GreaterEq,
+        Operator::GreaterEq,
+        3,
+        Associativity::Left,
+    ),
+    // bitwise operators bind like in Python, so `x & 1 == 0` compares `x & 1`
+    InfixOperator::new(TokenType::BitOr, Operator::BitOr, 4, Associativity::Left),
+    InfixOperator::new(TokenType::Xor, Operator::Xor, 5, Associativity::Left),
+    InfixOperator::new(TokenType::BitAnd, Operator::BitAnd, 6, Associativity::Left),
+    InfixOperator::new(
+        TokenType::ShiftLeft,
+        Operator::ShiftLeft,
+        7,
+        Associativity::Left,
+    ),
+    InfixOperator::new(
+        TokenType::ShiftRight,
+        Operator::ShiftRight,
+        7,
+        Associativity::Left,
+    ),
+    InfixOperator::new(TokenType::Add, Operator::Plus, 8, Associativity::Left),
+    InfixOperator::new(TokenType::Sub, Operator::Minus, 8, Associativity::Left),
+    InfixOperator::new(TokenType::Mult, Operator::Mult, 9, Associativity::Left),
+    InfixOperator::new(TokenType::Div, Operator::Div, 9, Associativity::Left),
+    InfixOperator::new(TokenType::Exp, Operator::Pow, 11, Associativity::Right),
+];
+
+// juxtaposition like `2x` or `3(x+1)` binds tighter than `*` and `/` so `1/2x` is `1/(2x)`
+const IMPLICIT_MULT: InfixOperator =
+    InfixOperator::new(TokenType::Mult, Operator::Mult, 10, Associativity::Left);
+
+// unary +/-/~ bind looser than `^` so `-2^2` is `-(2^2)`, and stop `2^3x` at `2^3`
+const PREFIX_BINDING_POWER: u8 = 21;
+
+// `not` takes a whole comparison so `not x < 1` is `not (x < 1)`
+const NOT_BINDING_POWER: u8 = 6;
+
 pub struct Parser {
     text: String,
     tokens: Vec<Token>,
@@ -39,7 +137,7 @@ impl Parser {
     }
 
     fn error(&self, token: &Token, expected: TokenType) -> anyhow::Error {
-        let mut arrow = " ".repeat(token.start - 1);
+        let mut arrow = " ".repeat(token.start);
         arrow.push('^');
         anyhow::anyhow!(
             "Invalid syntax, expected {:?}\n{}\n{}",
@@ -64,169 +162,294 @@ impl Parser {
         match token.token {
             TokenType::Number => {
                 self.eat(TokenType::Number)?;
-                let num = token.value.parse()?;
-                Ok(Node::Number(num))
+                let num = match bits::parse_literal(&token.value) {
+                    Some(num) => num?,
+                    None => Value::from_decimal_str(&token.value)?,
+                };
+                if self.current_token()?.token == TokenType::Unit {
+                    let unit = Unit::parse(&self.current_token()?.value)?;
+                    self.eat(TokenType::Unit)?;
+                    Ok(Node::Quantity(num, unit))
+                } else {
+                    Ok(Node::Number(num))
+                }
+            }
+            TokenType::Add => {
+                self.eat(TokenType::Add)?;
+                let operand = self.expr_bp(PREFIX_BINDING_POWER)?;
+                Ok(Node::Unary(UnaryOperator::Plus, Box::new(operand)))
+            }
+            TokenType::Sub => {
+                self.eat(TokenType::Sub)?;
+                let operand = self.expr_bp(PREFIX_BINDING_POWER)?;
+                Ok(Node::Unary(UnaryOperator::Minus, Box::new(operand)))
+            }
+            // `√2` is sqrt(2) and binds like a unary minus
+            TokenType::Sqrt => {
+                self.eat(TokenType::Sqrt)?;
+                let operand = self.expr_bp(PREFIX_BINDING_POWER)?;
+                Ok(Node::Function(String::from("sqrt"), vec![operand]))
+            }
+            TokenType::Tilde => {
+                self.eat(TokenType::Tilde)?;
+                let operand = self.expr_bp(PREFIX_BINDING_POWER)?;
+                Ok(Node::Unary(UnaryOperator::BitNot, Box::new(operand)))
+            }
+            TokenType::Not => {
+                self.eat(TokenType::Not)?;
+                let operand = self.expr_bp(NOT_BINDING_POWER)?;
+                Ok(Node::Unary(UnaryOperator::Not, Box::new(operand)))
             }
             TokenType::LParen => {
+                if let Some((count, end)) = self.param_list(self.current_token) {
+                    if self.token_type_at(end) == Some(&TokenType::Arrow) {
+                        self.eat(TokenType::LParen)?;
+                        let vars = self.params(count)?;
+                        self.eat(TokenType::RParen)?;
+                        return self.lambda(vars);
+                    }
+                }
+
                 self.eat(TokenType::LParen)?;
                 let result = self.expr()?;
                 self.eat(TokenType::RParen)?;
                 Ok(result)
             }
+            // `[1, 2, 3]` is a list and `[1, 2; 3, 4]` a matrix with rows split by `;`
+            TokenType::LBracket => {
+                self.eat(TokenType::LBracket)?;
+                if self.current_token()?.token == TokenType::RBracket {
+                    self.eat(TokenType::RBracket)?;
+                    return Ok(Node::List(Vec::new()));
+                }
+
+                let mut rows = vec![self.row()?];
+                while self.current_token()?.token == TokenType::Semicolon {
+                    self.eat(TokenType::Semicolon)?;
+                    rows.push(self.row()?);
+                }
+                self.eat(TokenType::RBracket)?;
+
+                if rows.len() == 1 {
+                    Ok(Node::List(rows.remove(0)))
+                } else {
+                    Ok(Node::Matrix(rows))
+                }
+            }
+            // the else branch reaches as far as it can, like the right side of `=`
+            TokenType::If => {
+                self.eat(TokenType::If)?;
+                let cond = self.expr()?;
+                self.eat(TokenType::Then)?;
+                let then = self.expr()?;
+                self.eat(TokenType::Else)?;
+                let otherwise = self.expr()?;
+                Ok(Node::If(
+                    Box::new(cond),
+                    Box::new(then),
+                    Box::new(otherwise),
+                ))
+            }
             TokenType::Ident => {
                 self.eat(TokenType::Ident)?;
+                if self.current_token()?.token == TokenType::Arrow {
+                    return self.lambda(vec![token.value]);
+                }
+
+                let mut name = token.value;
+                // `f'` names the derivative of `f`
+                while self.current_token()?.token == TokenType::Prime {
+                    self.eat(TokenType::Prime)?;
+                    name.push('\'');
+                }
+
                 if self.current_token()?.token == TokenType::LParen {
                     self.eat(TokenType::LParen)?;
-                    let inner = self.expr()?;
+                    let mut args = vec![self.expr()?];
+                    while self.current_token()?.token == TokenType::Comma {
+                        self.eat(TokenType::Comma)?;
+                        args.push(self.expr()?);
+                    }
                     self.eat(TokenType::RParen)?;
-                    Ok(Node::Function(token.value, Box::new(inner)))
+                    Ok(Node::Function(name, args))
                 } else {
-                    Ok(Node::Const(token.value))
+                    Ok(Node::Const(name))
                 }
             }
             _ => Err(self.error(&token, TokenType::Number)),
         }
     }
 
-    fn exp(&mut self) -> anyhow::Result<Node> {
+    fn row(&mut self) -> anyhow::Result<Vec<Node>> {
+        let mut items = vec![self.expr()?];
+        while self.current_token()?.token == TokenType::Comma {
+            self.eat(TokenType::Comma)?;
+            items.push(self.expr()?);
+        }
+        Ok(items)
+    }
+
+    fn infix_operator(token: &TokenType) -> Option<&'static InfixOperator> {
+        INFIX_OPERATORS.iter().find(|op| &op.token == token)
+    }
+
+    fn expr(&mut self) -> anyhow::Result<Node> {
+        let mut result = self.expr_bp(0)?;
+
+        if self.current_token()?.token == TokenType::Range {
+            self.eat(TokenType::Range)?;
+            result = Node::Range(Box::new(result), Box::new(self.expr_bp(0)?));
+        }
+
+        if self.current_token()?.token == TokenType::To {
+            self.eat(TokenType::To)?;
+            let unit = Unit::parse(&self.current_token()?.value)?;
+            self.eat(TokenType::Unit)?;
+            return Ok(Node::Convert(Box::new(result), unit));
+        }
+        Ok(result)
+    }
+
+    // precedence climbing, keeps folding operators that bind at least as tightly as min_bp
+    fn expr_bp(&mut self, min_bp: u8) -> anyhow::Result<Node> {
         let mut result = self.factor()?;
 
-        while self.current_token()?.token == TokenType::Exp {
-            let token = self.current_token()?.clone();
-            if token.token == TokenType::Exp {
-                self.eat(TokenType::Exp)?;
-                result = Node::Expr(
-                    Box::new(result),
-                    crate::ast::Operator::Pow,
-                    Box::new(self.factor()?),
-                )
+        // indexing binds tighter than any operator
+        while self.current_token()?.token == TokenType::LBracket {
+            self.eat(TokenType::LBracket)?;
+            let index = self.expr()?;
+            self.eat(TokenType::RBracket)?;
+            result = Node::Index(Box::new(result), Box::new(index));
+        }
+
+        loop {
+            let token = &self.current_token()?.token;
+            let (infix, implicit) = match Self::infix_operator(token) {
+                Some(infix) => (infix, false),
+                None if matches!(token, TokenType::Ident | TokenType::LParen) => {
+                    (&IMPLICIT_MULT, true)
+                }
+                None => break,
+            };
+
+            let (left_bp, right_bp) = infix.binding_power();
+            if left_bp < min_bp {
+                break;
+            }
+
+            if !implicit {
+                self.eat(infix.token.clone())?;
             }
+            result = Node::Expr(
+                Box::new(result),
+                infix.op.clone(),
+                Box::new(self.expr_bp(right_bp)?),
+            );
         }
         Ok(result)
     }
 
-    fn term(&mut self) -> anyhow::Result<Node> {
-        let mut result = self.exp()?;
+    fn token_type_at(&self, i: usize) -> Option<&TokenType> {
+        self.tokens.get(i).map(|token| &token.token)
+    }
 
-        while self.current_token()?.token == TokenType::Mult
-            || self.current_token()?.token == TokenType::Div
-        {
-            let token = self.current_token()?.clone();
-            if token.token == TokenType::Mult {
-                self.eat(TokenType::Mult)?;
-                result = Node::Expr(
-                    Box::new(result),
-                    crate::ast::Operator::Mult,
-                    Box::new(self.exp()?),
-                )
-            } else if token.token == TokenType::Div {
-                self.eat(TokenType::Div)?;
-                result = Node::Expr(
-                    Box::new(result),
-                    crate::ast::Operator::Div,
-                    Box::new(self.exp()?),
-                )
+    // look ahead for `(a, b, ...)` at i, returns the number of parameters and the index after it
+    fn param_list(&self, mut i: usize) -> Option<(usize, usize)> {
+        if self.token_type_at(i) != Some(&TokenType::LParen) {
+            return None;
+        }
+        i += 1;
+
+        let mut count = 0;
+        loop {
+            if self.token_type_at(i) != Some(&TokenType::Ident) {
+                return None;
+            }
+            count += 1;
+            i += 1;
+
+            match self.token_type_at(i) {
+                Some(TokenType::Comma) => i += 1,
+                Some(TokenType::RParen) => return Some((count, i + 1)),
+                _ => return None,
             }
         }
-        Ok(result)
     }
 
-    fn expr(&mut self) -> anyhow::Result<Node> {
-        let result = self.term()?;
-        self.expr_cont(result)
+    // look ahead for `name(a, b, ...) =`, returns the number of parameters
+    fn func_def_params(&self) -> Option<usize> {
+        if self.token_type_at(self.current_token) != Some(&TokenType::Ident) {
+            return None;
+        }
+        let (count, end) = self.param_list(self.current_token + 1)?;
+        (self.token_type_at(end) == Some(&TokenType::Assign)).then_some(count)
     }
 
-    fn expr_cont(&mut self, mut result: Node) -> anyhow::Result<Node> {
-        while self.current_token()?.token == TokenType::Add
-            || self.current_token()?.token == TokenType::Sub
-        {
-            let token = self.current_token()?.clone();
-            if token.token == TokenType::Add {
-                self.eat(TokenType::Add)?;
-                result = Node::Expr(
-                    Box::new(result),
-                    crate::ast::Operator::Plus,
-                    Box::new(self.term()?),
-                )
-            } else if token.token == TokenType::Sub {
-                self.eat(TokenType::Sub)?;
-                result = Node::Expr(
-                    Box::new(result),
-                    crate::ast::Operator::Minus,
-                    Box::new(self.term()?),
-                )
+    // `(a, b, ...)` with the parentheses already eaten
+    fn params(&mut self, count: usize) -> anyhow::Result<Vec<String>> {
+        let mut var_names = Vec::with_capacity(count);
+        for i in 0..count {
+            if i > 0 {
+                self.eat(TokenType::Comma)?;
             }
+            var_names.push(self.current_token()?.clone().value);
+            self.eat(TokenType::Ident)?;
         }
-        Ok(result)
+        Ok(var_names)
+    }
+
+    // the body reaches as far as it can, like the right side of `=`
+    fn lambda(&mut self, vars: Vec<String>) -> anyhow::Result<Node> {
+        self.eat(TokenType::Arrow)?;
+        Ok(Node::Lambda(vars, Box::new(self.expr()?)))
     }
 
     // look ahead parser
     fn assign(&mut self) -> anyhow::Result<Node> {
-        if self
-            .tokens
-            .get(self.current_token)
-            .map(|token| &token.token)
-            == Some(&TokenType::Ident)
-            && self
-                .tokens
-                .get(self.current_token + 1)
-                .map(|token| &token.token)
-                == Some(&TokenType::Assign)
+        if self.token_type_at(self.current_token) == Some(&TokenType::Ident)
+            && self.token_type_at(self.current_token + 1) == Some(&TokenType::Assign)
         {
             let const_name = self.current_token()?.clone().value;
 
             self.eat(TokenType::Ident)?;
             self.eat(TokenType::Assign)?;
 
-            return Ok(Node::AssignConst(const_name, Box::new(self.expr()?)));
-        } else if self
-            .tokens
-            .get(self.current_token)
-            .map(|token| &token.token)
-            == Some(&TokenType::Ident)
-            && self
-                .tokens
-                .get(self.current_token + 1)
-                .map(|token| &token.token)
-                == Some(&TokenType::LParen)
-            && self
-                .tokens
-                .get(self.current_token + 2)
-                .map(|token| &token.token)
-                == Some(&TokenType::Ident)
-            && self
-                .tokens
-                .get(self.current_token + 3)
-                .map(|token| &token.token)
-                == Some(&TokenType::RParen)
-            && self
-                .tokens
-                .get(self.current_token + 4)
-                .map(|token| &token.token)
-                == Some(&TokenType::Assign)
-        {
+            Ok(Node::AssignConst(const_name, Box::new(self.expr()?)))
+        } else if let Some(param_count) = self.func_def_params() {
             let func_name = self.current_token()?.clone().value;
 
             self.eat(TokenType::Ident)?;
             self.eat(TokenType::LParen)?;
-
-            let var_name = self.current_token()?.clone().value;
-            self.eat(TokenType::Ident)?;
-
+            let var_names = self.params(param_count)?;
             self.eat(TokenType::RParen)?;
             self.eat(TokenType::Assign)?;
 
-            return Ok(Node::AssignFunc(func_name, var_name, Box::new(self.expr()?)));
+            Ok(Node::AssignFunc(
+                func_name,
+                var_names,
+                Box::new(self.expr()?),
+            ))
         } else {
             self.expr()
         }
-
     }
-    pub fn calc(&mut self) -> anyhow::Result<Node> {
-        let res = self.assign()?;
-
-        self.eat(TokenType::EOI)?;
 
-        Ok(res)
+    // statements split by `;`, which inside brackets splits matrix rows instead,
+    // an empty line or one with only a comment has none
+    pub fn calc(&mut self) -> anyhow::Result<Vec<Node>> {
+        let mut statements = Vec::new();
+        loop {
+            match self.current_token()?.token {
+                TokenType::Semicolon => self.eat(TokenType::Semicolon)?,
+                TokenType::Eoi => return Ok(statements),
+                _ => {
+                    statements.push(self.assign()?);
+                    if self.current_token()?.token != TokenType::Semicolon {
+                        self.eat(TokenType::Eoi)?;
+                        return Ok(statements);
+                    }
+                }
+            }
+        }
     }
 }
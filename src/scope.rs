@@ -0,0 +1,64 @@
+use std::{collections::HashMap, fmt::Display, rc::Rc};
+
+use crate::{ast::Node, value::Value};
+
+// local variables of a call, lookups fall back to the enclosing scope
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Scope {
+    pub vars: HashMap<String, Value>,
+    pub parent: Option<Rc<Scope>>,
+}
+
+impl Scope {
+    pub fn new(vars: HashMap<String, Value>, parent: Option<Rc<Scope>>) -> Self {
+        Self { vars, parent }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.vars
+            .get(name)
+            .or_else(|| self.parent.as_ref()?.get(name))
+    }
+}
+
+// a function value, remembers the scope it was made in
+#[derive(Debug, Clone, PartialEq)]
+pub struct Closure {
+    pub vars: Vec<String>,
+    pub body: Node,
+    pub scope: Option<Rc<Scope>>,
+    pub name: Option<String>,
+}
+
+impl Closure {
+    pub fn new(vars: Vec<String>, body: Node, scope: Option<Rc<Scope>>) -> Self {
+        Self {
+            vars,
+            body,
+            scope,
+            name: None,
+        }
+    }
+
+    pub fn named(self, name: &str) -> Self {
+        Self {
+            name: Some(name.to_owned()),
+            ..self
+        }
+    }
+
+    // what errors and stack traces call the function, anonymous ones are `lambda`
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or("lambda")
+    }
+}
+
+impl Display for Closure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            Node::Lambda(self.vars.clone(), Box::new(self.body.clone()))
+        )
+    }
+}
@@ -0,0 +1,363 @@
+use crate::{
+    ast::{Node, Operator, UnaryOperator},
+    value::Value,
+};
+
+// stops rewriting once nothing changes or after this many passes
+const MAX_PASSES: usize = 16;
+
+pub fn simplify(node: Node) -> Node {
+    let mut node = node;
+    for _ in 0..MAX_PASSES {
+        let next = simplify_once(node.clone());
+        if next == node {
+            break;
+        }
+        node = next;
+    }
+    node
+}
+
+fn one() -> Value {
+    Value::from(1)
+}
+
+fn is_negative(value: &Value) -> bool {
+    *value < Value::default()
+}
+
+fn expr(lhs: Node, op: Operator, rhs: Node) -> Node {
+    Node::Expr(Box::new(lhs), op, Box::new(rhs))
+}
+
+fn simplify_once(node: Node) -> Node {
+    match node {
+        Node::Expr(lhs, op, rhs) => {
+            let (lhs, rhs) = (simplify_once(*lhs), simplify_once(*rhs));
+            if let (Node::Number(a), Node::Number(b)) = (&lhs, &rhs) {
+                if let Some(folded) = fold(a.clone(), &op, b.clone()) {
+                    return Node::Number(folded);
+                }
+            }
+
+            match op {
+                Operator::Plus | Operator::Minus => simplify_sum(expr(lhs, op, rhs)),
+                Operator::Mult | Operator::Div => simplify_product(expr(lhs, op, rhs)),
+                Operator::Pow => match (&lhs, &rhs) {
+                    (_, Node::Number(exp)) if *exp == Value::default() => Node::Number(one()),
+                    (_, Node::Number(exp)) if *exp == one() => lhs,
+                    (Node::Number(base), _) if *base == one() => lhs,
+                    _ => expr(lhs, op, rhs),
+                },
+                op => expr(lhs, op, rhs),
+            }
+        }
+        Node::Unary(UnaryOperator::Plus, node) => simplify_once(*node),
+        Node::Unary(UnaryOperator::Not, node) => {
+            Node::Unary(UnaryOperator::Not, Box::new(simplify_once(*node)))
+        }
+        Node::Unary(UnaryOperator::Minus, node) => simplify_sum(Node::Unary(
+            UnaryOperator::Minus,
+            Box::new(simplify_once(*node)),
+        )),
+        Node::Function(name, args) => {
+            Node::Function(name, args.into_iter().map(simplify_once).collect())
+        }
+        Node::List(items) => Node::List(items.into_iter().map(simplify_once).collect()),
+        Node::Matrix(rows) => Node::Matrix(
+            rows.into_iter()
+                .map(|row| row.into_iter().map(simplify_once).collect())
+                .collect(),
+        ),
+        Node::Convert(node, unit) => Node::Convert(Box::new(simplify_once(*node)), unit),
+        Node::If(cond, then, otherwise) => match simplify_once(*cond) {
+            // a constant condition picks its branch
+            Node::Number(cond) => match cond.is_true() {
+                Ok(true) => simplify_once(*then),
+                Ok(false) => simplify_once(*otherwise),
+                Err(_) => Node::If(Box::new(Node::Number(cond)), then, otherwise),
+            },
+            cond => Node::If(
+                Box::new(cond),
+                Box::new(simplify_once(*then)),
+                Box::new(simplify_once(*otherwise)),
+            ),
+        },
+        node => node,
+    }
+}
+
+fn fold(lhs: Value, op: &Operator, rhs: Value) -> Option<Value> {
+    match op {
+        Operator::Plus => lhs.checked_add(rhs).ok(),
+        Operator::Minus => lhs.checked_sub(rhs).ok(),
+        Operator::Mult => Some(lhs * rhs),
+        Operator::Div => lhs.checked_div(rhs).ok(),
+        Operator::Pow => lhs.pow(rhs).ok(),
+        _ => None,
+    }
+}
+
+// splits `3 * x` into (3, x) and `3 * x / y` into (3, x / y), plain numbers have no term
+fn split_coefficient(node: Node) -> (Value, Option<Node>) {
+    match node {
+        Node::Number(num) => (num, None),
+        Node::Expr(lhs, Operator::Mult, rhs) => match (*lhs, *rhs) {
+            (Node::Number(num), term) | (term, Node::Number(num)) => (num, Some(term)),
+            (lhs, rhs) => (one(), Some(expr(lhs, Operator::Mult, rhs))),
+        },
+        Node::Expr(lhs, Operator::Div, rhs) => {
+            let (coefficient, numerator) = split_coefficient(*lhs);
+            let numerator = numerator.unwrap_or_else(|| Node::Number(one()));
+            (coefficient, Some(expr(numerator, Operator::Div, *rhs)))
+        }
+        node => (one(), Some(node)),
+    }
+}
+
+// coefficient * term, written `c * x / y` rather than `c * (x / y)`
+fn scale(coefficient: Value, term: Node) -> Node {
+    match term {
+        Node::Expr(lhs, Operator::Div, rhs) => {
+            let numerator = match *lhs {
+                Node::Number(num) if num == one() => Node::Number(coefficient),
+                lhs => scale(coefficient, lhs),
+            };
+            expr(numerator, Operator::Div, *rhs)
+        }
+        term if coefficient == one() => term,
+        term => expr(Node::Number(coefficient), Operator::Mult, term),
+    }
+}
+
+fn collect_terms(node: Node, negate: bool, terms: &mut Vec<(Value, Option<Node>)>) {
+    match node {
+        Node::Expr(lhs, Operator::Plus, rhs) => {
+            collect_terms(*lhs, negate, terms);
+            collect_terms(*rhs, negate, terms);
+        }
+        Node::Expr(lhs, Operator::Minus, rhs) => {
+            collect_terms(*lhs, negate, terms);
+            collect_terms(*rhs, !negate, terms);
+        }
+        Node::Unary(UnaryOperator::Minus, node) => collect_terms(*node, !negate, terms),
+        Node::Unary(UnaryOperator::Plus, node) => collect_terms(*node, negate, terms),
+        node => {
+            let (coefficient, term) = split_coefficient(node);
+            let coefficient = if negate { -coefficient } else { coefficient };
+
+            // like terms are combined by adding their coefficients
+            if let Some((existing, _)) = terms.iter_mut().find(|(_, other)| *other == term) {
+                if let Ok(sum) = existing.clone().checked_add(coefficient.clone()) {
+                    *existing = sum;
+                    return;
+                }
+            }
+            terms.push((coefficient, term));
+        }
+    }
+}
+
+fn simplify_sum(node: Node) -> Node {
+    let mut terms = Vec::new();
+    collect_terms(node, false, &mut terms);
+
+    // the constant goes last so `1 + x` reads as `x + 1`
+    terms.sort_by_key(|(_, term)| term.is_none());
+
+    let mut result: Option<Node> = None;
+    for (coefficient, term) in terms {
+        if coefficient == Value::default() {
+            continue;
+        }
+
+        let negative = is_negative(&coefficient);
+        let magnitude = if negative {
+            -coefficient.clone()
+        } else {
+            coefficient.clone()
+        };
+
+        result = Some(match (result, term) {
+            (None, None) => Node::Number(coefficient),
+            (None, Some(term)) if magnitude == one() && negative => {
+                Node::Unary(UnaryOperator::Minus, Box::new(term))
+            }
+            (None, Some(term)) => scale(coefficient, term),
+            (Some(acc), term) => {
+                let op = if negative {
+                    Operator::Minus
+                } else {
+                    Operator::Plus
+                };
+                let term = match term {
+                    None => Node::Number(magnitude),
+                    Some(term) => scale(magnitude, term),
+                };
+                expr(acc, op, term)
+            }
+        });
+    }
+
+    result.unwrap_or_else(|| Node::Number(Value::default()))
+}
+
+fn collect_factors(
+    node: Node,
+    invert: bool,
+    coefficient: &mut Value,
+    factors: &mut Vec<(Node, Value)>,
+) {
+    match node {
+        Node::Expr(lhs, Operator::Mult, rhs) => {
+            collect_factors(*lhs, invert, coefficient, factors);
+            collect_factors(*rhs, invert, coefficient, factors);
+        }
+        Node::Expr(lhs, Operator::Div, rhs) => {
+            collect_factors(*lhs, invert, coefficient, factors);
+            collect_factors(*rhs, !invert, coefficient, factors);
+        }
+        Node::Unary(UnaryOperator::Minus, node) => {
+            *coefficient = -coefficient.clone();
+            collect_factors(*node, invert, coefficient, factors);
+        }
+        Node::Number(num) if !invert => *coefficient = coefficient.clone() * num,
+        Node::Number(num) if num != Value::default() => {
+            if let Ok(quotient) = coefficient.clone().checked_div(num) {
+                *coefficient = quotient;
+            }
+        }
+        node => {
+            let (base, exp) = match node {
+                Node::Expr(base, Operator::Pow, exp) => match *exp {
+                    Node::Number(exp) => (*base, exp),
+                    exp => (expr(*base, Operator::Pow, exp), one()),
+                },
+                node => (node, one()),
+            };
+            let exp = if invert { -exp } else { exp };
+
+            // a base repeated next to itself is combined by adding the exponents, bases further
+            // apart stay where they are since matrix products do not commute
+            if let Some((last, existing)) = factors.last_mut() {
+                if *last == base {
+                    if let Ok(sum) = existing.clone().checked_add(exp.clone()) {
+                        *existing = sum;
+                        if *existing == Value::default() {
+                            factors.pop();
+                        }
+                        return;
+                    }
+                }
+            }
+            factors.push((base, exp));
+        }
+    }
+}
+
+fn product(nodes: Vec<Node>) -> Option<Node> {
+    nodes
+        .into_iter()
+        .reduce(|acc, node| expr(acc, Operator::Mult, node))
+}
+
+fn simplify_product(node: Node) -> Node {
+    let mut coefficient = one();
+    let mut factors = Vec::new();
+    collect_factors(node, false, &mut coefficient, &mut factors);
+
+    if coefficient == Value::default() {
+        return Node::Number(coefficient);
+    }
+
+    factors.retain(|(_, exp)| *exp != Value::default());
+    let power = |base: Node, exp: Value| {
+        if exp == one() {
+            base
+        } else {
+            expr(base, Operator::Pow, Node::Number(exp))
+        }
+    };
+
+    // a lone minus sign is printed as negation, other coefficients keep their sign
+    let leading_divide = factors.first().is_none_or(|(_, exp)| is_negative(exp));
+    let negate = coefficient == -one() && !leading_divide;
+    let mut result = (coefficient != one() && !negate).then_some(Node::Number(coefficient));
+
+    // the factors keep their order, with neighbouring divisors grouped as `a / (b * c)`
+    for run in factors.chunk_by(|(_, a), (_, b)| is_negative(a) == is_negative(b)) {
+        if is_negative(&run[0].1) {
+            let divisors = run
+                .iter()
+                .map(|(base, exp)| power(base.clone(), -exp.clone()))
+                .collect();
+            let divisor = product(divisors).unwrap_or_else(|| Node::Number(one()));
+            let acc = result.unwrap_or_else(|| Node::Number(one()));
+            result = Some(expr(acc, Operator::Div, divisor));
+        } else {
+            for (base, exp) in run {
+                let factor = power(base.clone(), exp.clone());
+                result = Some(match result {
+                    Some(acc) => expr(acc, Operator::Mult, factor),
+                    None => factor,
+                });
+            }
+        }
+    }
+    let result = result.unwrap_or_else(|| Node::Number(one()));
+
+    if negate {
+        Node::Unary(UnaryOperator::Minus, Box::new(result))
+    } else {
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn simplified(text: &str) -> String {
+        let node = Parser::new(text.into()).unwrap().calc().unwrap().remove(0);
+        simplify(node).to_string()
+    }
+
+    #[test]
+    fn like_terms() {
+        let cases = [
+            ("x*x*3 - x + 2*x - 5", "3 * x^2 + x - 5"),
+            ("x * 2 * x", "2 * x^2"),
+            ("x - (y - x)", "2 * x - y"),
+            ("x - x", "0"),
+            // quotients with the same divisor
+            ("x^2 * x^-2", "1"),
+            ("2 / x + 3 / x", "5 / x"),
+            ("x/y - 2*x/y", "-(x / y)"),
+            ("3 * x / 3", "x"),
+            // matrix products do not commute so only the same order cancels
+            ("a * b * a - a * b * a", "0"),
+            ("a * b - b * a", "a * b - b * a"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(simplified(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn identities() {
+        let cases = [
+            ("0 * x + 1 * y", "y"),
+            ("x^1 + y^0", "x + 1"),
+            ("2 * 3 + x", "x + 6"),
+            ("-(-x)", "x"),
+            ("+x", "x"),
+            ("if 1 then x else y", "x"),
+            ("sin(0 + x)", "sin(x)"),
+            ("[1 + 1, x * 1]", "[2, x]"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(simplified(input), expected, "{}", input);
+        }
+    }
+}
@@ -0,0 +1,452 @@
+use std::f64::consts::{E, PI};
+
+// stop summing series once terms are this small relative to the total
+const EPSILON: f64 = 1e-16;
+const MAX_TERMS: usize = 500;
+// erf is summed as a series below this and erfc as a continued fraction above it
+const ERF_SPLIT: f64 = 2.5;
+// gamma of larger arguments overflows
+const MAX_GAMMA: f64 = 171.;
+const EULER_GAMMA: f64 = 0.577_215_664_901_532_9;
+// terms of the alternating series zeta is computed from
+const ZETA_TERMS: usize = 50;
+// Miller's recurrence rescales its values when they grow past this
+const RESCALE: f64 = 1e250;
+// Bessel functions of larger x use Hankel's expansion since Miller's recurrence needs about x terms
+const BESSEL_ASYMPTOTIC: f64 = 1e4;
+
+const LANCZOS_G: f64 = 7.;
+const LANCZOS: [f64; 9] = [
+    0.999_999_999_999_809_9,
+    676.520_368_121_885_1,
+    -1_259.139_216_722_402_8,
+    771.323_428_777_653_1,
+    -176.615_029_162_140_6,
+    12.507_343_278_686_905,
+    -0.138_571_095_265_720_12,
+    9.984_369_578_019_572e-6,
+    1.505_632_735_149_311_6e-7,
+];
+
+fn lanczos_sum(x: f64) -> f64 {
+    LANCZOS[1..]
+        .iter()
+        .enumerate()
+        .fold(LANCZOS[0], |acc, (i, c)| acc + c / (x + i as f64 + 1.))
+}
+
+// exact for whole numbers, the Lanczos approximation otherwise
+pub fn gamma(x: f64) -> f64 {
+    if x.fract() == 0. && x <= 0. {
+        return f64::NAN;
+    }
+    if x.fract() == 0. && x <= MAX_GAMMA {
+        return (1..x as u64).map(|i| i as f64).product();
+    }
+    if x < 0.5 {
+        return PI / ((PI * x).sin() * gamma(1. - x));
+    }
+
+    let x = x - 1.;
+    let t = x + LANCZOS_G + 0.5;
+    // split in two so the power does not overflow before the exponential shrinks it
+    let half = t.powf((x + 0.5) / 2.);
+    (2. * PI).sqrt() * half * (half * (-t).exp()) * lanczos_sum(x)
+}
+
+// ln|gamma(x)|, stays finite where gamma itself overflows
+pub fn lgamma(x: f64) -> f64 {
+    if x.fract() == 0. && x <= 0. {
+        return f64::INFINITY;
+    }
+    if x < 0.5 {
+        return (PI / (PI * x).sin()).abs().ln() - lgamma(1. - x);
+    }
+
+    let x = x - 1.;
+    let t = x + LANCZOS_G + 0.5;
+    0.5 * (2. * PI).ln() + (x + 0.5) * t.ln() - t + lanczos_sum(x).ln()
+}
+
+pub fn beta(a: f64, b: f64) -> f64 {
+    if a > 0. && b > 0. && a + b > MAX_GAMMA {
+        return (lgamma(a) + lgamma(b) - lgamma(a + b)).exp();
+    }
+    gamma(a) * gamma(b) / gamma(a + b)
+}
+
+pub fn erf(x: f64) -> f64 {
+    if x < 0. {
+        return -erf(-x);
+    }
+    if x < ERF_SPLIT {
+        erf_series(x)
+    } else {
+        1. - erfc_fraction(x)
+    }
+}
+
+pub fn erfc(x: f64) -> f64 {
+    if x < ERF_SPLIT {
+        1. - erf(x)
+    } else {
+        erfc_fraction(x)
+    }
+}
+
+// 2/sqrt(pi) e^(-x^2) sum 2^n x^(2n+1) / (1 3 5 ... (2n+1)), every term is positive
+fn erf_series(x: f64) -> f64 {
+    let mut term = x;
+    let mut sum = x;
+    for n in 1..MAX_TERMS {
+        term *= 2. * x * x / (2 * n + 1) as f64;
+        sum += term;
+        if term < EPSILON * sum {
+            break;
+        }
+    }
+    2. / PI.sqrt() * (-x * x).exp() * sum
+}
+
+// e^(-x^2) / sqrt(pi) / (x + (1/2) / (x + 1 / (x + (3/2) / (x + ...)))) by Lentz's method
+fn erfc_fraction(x: f64) -> f64 {
+    let tiny = 1e-300;
+    let mut f = x;
+    let mut c = x;
+    let mut d = 0.;
+    for n in 1..MAX_TERMS {
+        let a = n as f64 / 2.;
+        d = x + a * d;
+        d = if d == 0. { 1. / tiny } else { 1. / d };
+        c = x + a / c;
+        if c == 0. {
+            c = tiny;
+        }
+        let delta = c * d;
+        f *= delta;
+        if (delta - 1.).abs() < EPSILON {
+            break;
+        }
+    }
+    (-x * x).exp() / PI.sqrt() / f
+}
+
+// regularized incomplete beta function I_x(a, b)
+pub fn beta_inc(a: f64, b: f64, x: f64) -> f64 {
+    if x <= 0. {
+        return 0.;
+    }
+    if x >= 1. {
+        return 1.;
+    }
+
+    let front = (lgamma(a + b) - lgamma(a) - lgamma(b) + a * x.ln() + b * (1. - x).ln()).exp();
+    // the continued fraction converges quickly on this side, the other side uses symmetry
+    if x < (a + 1.) / (a + b + 2.) {
+        front * beta_fraction(a, b, x) / a
+    } else {
+        1. - front * beta_fraction(b, a, 1. - x) / b
+    }
+}
+
+fn beta_fraction(a: f64, b: f64, x: f64) -> f64 {
+    let tiny = 1e-300;
+    let mut c = 1.;
+    let mut d = 1. - (a + b) * x / (a + 1.);
+    d = if d.abs() < tiny { 1. / tiny } else { 1. / d };
+    let mut h = d;
+
+    for m in 1..MAX_TERMS {
+        let m = m as f64;
+        for numer in [
+            m * (b - m) * x / ((a + 2. * m - 1.) * (a + 2. * m)),
+            -(a + m) * (a + b + m) * x / ((a + 2. * m) * (a + 2. * m + 1.)),
+        ] {
+            d = 1. + numer * d;
+            d = if d.abs() < tiny { 1. / tiny } else { 1. / d };
+            c = 1. + numer / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            h *= d * c;
+        }
+        if (d * c - 1.).abs() < EPSILON {
+            break;
+        }
+    }
+    h
+}
+
+// Borwein's algorithm for the alternating series, reflected for s < 0
+pub fn zeta(s: f64) -> f64 {
+    if s == 1. {
+        return f64::INFINITY;
+    }
+    if s < 0. {
+        // the trivial zeros
+        if s % 2. == 0. {
+            return 0.;
+        }
+        return 2f64.powf(s) * PI.powf(s - 1.) * (PI * s / 2.).sin() * gamma(1. - s) * zeta(1. - s);
+    }
+
+    let n = ZETA_TERMS as f64;
+    let mut d = Vec::with_capacity(ZETA_TERMS + 1);
+    let (mut term, mut sum) = (1., 1.);
+    d.push(sum);
+    for i in 1..=ZETA_TERMS {
+        let i = i as f64;
+        term *= 4. * (n + i - 1.) * (n - i + 1.) / ((2. * i) * (2. * i - 1.));
+        sum += term;
+        d.push(sum);
+    }
+
+    let last = d[ZETA_TERMS];
+    let eta = -(0..ZETA_TERMS)
+        .map(|k| {
+            let sign = if k % 2 == 0 { 1. } else { -1. };
+            sign * (d[k] - last) / (k as f64 + 1.).powf(s)
+        })
+        .sum::<f64>()
+        / last;
+    eta / (1. - 2f64.powf(1. - s))
+}
+
+// J_0(x) to J_max(x) by Miller's backward recurrence, normalized with J_0 + 2 J_2 + 2 J_4 + ... = 1
+fn bessel_j_orders(x: f64, max: usize) -> Vec<f64> {
+    if x == 0. {
+        let mut orders = vec![0.; max + 1];
+        orders[0] = 1.;
+        return orders;
+    }
+
+    // far enough above both the order and x for the start to be negligible
+    let top = max.max(x.abs() as usize) as f64;
+    let mut start = (top + 20. + 15. * top.cbrt()) as usize;
+    start += start % 2;
+
+    let mut f = vec![0.; start + 2];
+    f[start] = 1.;
+    for k in (1..=start).rev() {
+        f[k - 1] = 2. * k as f64 / x.abs() * f[k] - f[k + 1];
+        if f[k - 1].abs() > RESCALE {
+            f[k - 1..].iter_mut().for_each(|v| *v /= RESCALE);
+        }
+    }
+
+    let norm = f[0] + 2. * f[2..].iter().step_by(2).sum::<f64>();
+    f.truncate(max + 1);
+    f.iter_mut().enumerate().for_each(|(k, v)| {
+        *v /= norm;
+        // J_k(-x) = (-1)^k J_k(x)
+        if x < 0. && k % 2 == 1 {
+            *v = -*v;
+        }
+    });
+    f
+}
+
+// J_n(x) and Y_n(x) for large positive x from Hankel's asymptotic expansion
+fn bessel_hankel(n: u32, x: f64) -> (f64, f64) {
+    let mu = 4. * (n * n) as f64;
+    let (mut p, mut q, mut term) = (1., 0., 1f64);
+    for k in 1..MAX_TERMS {
+        let next = term * (mu - ((2 * k - 1) as f64).powi(2)) / (8. * k as f64 * x);
+        // the series diverges, so stop at its smallest term
+        if next.abs() >= term.abs() || next.abs() < EPSILON {
+            break;
+        }
+        term = next;
+        match k % 4 {
+            1 => q += term,
+            2 => p -= term,
+            3 => q -= term,
+            _ => p += term,
+        }
+    }
+
+    // cos(x - phase) expanded so the phase is not lost when rounding a huge x
+    let phase = (n as f64 / 2. + 0.25) * PI;
+    let cos = x.cos() * phase.cos() + x.sin() * phase.sin();
+    let sin = x.sin() * phase.cos() - x.cos() * phase.sin();
+    let scale = (2. / (PI * x)).sqrt();
+    (scale * (p * cos - q * sin), scale * (p * sin + q * cos))
+}
+
+// J_-n(x) = (-1)^n J_n(x), the same holds for Y
+fn reflect_order(n: i64, value: f64) -> f64 {
+    if n < 0 && n % 2 != 0 {
+        -value
+    } else {
+        value
+    }
+}
+
+pub fn bessel_j(n: i64, x: f64) -> f64 {
+    let k = n.unsigned_abs() as usize;
+    if x.abs() >= BESSEL_ASYMPTOTIC {
+        // forward recurrence is stable for J while the order is below x
+        let (mut prev, mut cur) = (bessel_hankel(0, x.abs()).0, bessel_hankel(1, x.abs()).0);
+        for i in 1..k {
+            (prev, cur) = (cur, 2. * i as f64 / x.abs() * cur - prev);
+        }
+        let value = if k == 0 { prev } else { cur };
+        let value = if x < 0. && k % 2 == 1 { -value } else { value };
+        return reflect_order(n, value);
+    }
+    reflect_order(n, bessel_j_orders(x, k)[k])
+}
+
+// Y_0 and Y_1 from Neumann series over J, x must be positive
+fn bessel_y01(x: f64) -> (f64, f64) {
+    if x >= BESSEL_ASYMPTOTIC {
+        return (bessel_hankel(0, x).1, bessel_hankel(1, x).1);
+    }
+    let j = bessel_j_orders(x, (x + 20. + 15. * x.cbrt()) as usize);
+    let log = 2. / PI * ((x / 2.).ln() + EULER_GAMMA);
+
+    let (mut y0, mut y1) = (log * j[0], log * j[1] - 2. / (PI * x) * j[0]);
+    for k in 1..(j.len() - 1) / 2 {
+        let sign = if k % 2 == 0 { 1. } else { -1. };
+        y0 -= 4. / PI * sign * j[2 * k] / k as f64;
+        y1 += 2. / PI * sign * (j[2 * k - 1] - j[2 * k + 1]) / k as f64;
+    }
+    (y0, y1)
+}
+
+// forward recurrence from Y_0 and Y_1, which is stable for Y
+pub fn bessel_y(n: i64, x: f64) -> f64 {
+    let (mut prev, mut cur) = bessel_y01(x);
+    if n == 0 {
+        return prev;
+    }
+    for k in 1..n.unsigned_abs() {
+        (prev, cur) = (cur, 2. * k as f64 / x * cur - prev);
+    }
+    reflect_order(n, cur)
+}
+
+// Halley's method, x >= -1/e
+pub fn lambertw(x: f64) -> f64 {
+    if x == -1. / E {
+        return -1.;
+    }
+
+    let mut w = if x < -0.25 {
+        // series around the branch point
+        let p = (2. * (E * x + 1.)).sqrt();
+        -1. + p - p * p / 3.
+    } else if x < E {
+        x.ln_1p()
+    } else {
+        x.ln() - x.ln().ln()
+    };
+    for _ in 0..MAX_TERMS {
+        let ew = w.exp();
+        let f = w * ew - x;
+        let step = f / (ew * (w + 1.) - (w + 2.) * f / (2. * w + 2.));
+        w -= step;
+        if step.abs() <= EPSILON * w.abs().max(1.) {
+            break;
+        }
+    }
+    w
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(name: &str, res: f64, expected: f64) {
+        assert!((res - expected).abs() < 1e-13, "{} = {}", name, res);
+    }
+
+    // reference values from Abramowitz and Stegun and the DLMF
+    #[test]
+    fn gamma_and_error_functions() {
+        assert_eq!(gamma(5.), 24.);
+        assert!(gamma(-1.).is_nan());
+        assert_eq!(lgamma(0.), f64::INFINITY);
+        let cases = [
+            ("gamma(1/2)", gamma(0.5), PI.sqrt()),
+            ("gamma(-1.5)", gamma(-1.5), 2.363_271_801_207_355),
+            ("lgamma(100)", lgamma(100.), 359.134_205_369_575_4),
+            ("beta(2, 3)", beta(2., 3.), 1. / 12.),
+            ("erf(0.5)", erf(0.5), 0.520_499_877_813_046_5),
+            ("erf(-1)", erf(-1.), -0.842_700_792_949_714_9),
+            ("erfc(3)", erfc(3.), 2.209_049_699_858_544e-5),
+        ];
+        for (name, res, expected) in cases {
+            assert_close(name, res, expected);
+        }
+    }
+
+    #[test]
+    fn zeta_values() {
+        assert_eq!(zeta(1.), f64::INFINITY);
+        assert_eq!(zeta(-2.), 0.);
+        let cases = [
+            ("zeta(2)", zeta(2.), PI.powi(2) / 6.),
+            ("zeta(3)", zeta(3.), 1.202_056_903_159_594_3),
+            ("zeta(1/2)", zeta(0.5), -1.460_354_508_809_586_8),
+            ("zeta(-1)", zeta(-1.), -1. / 12.),
+        ];
+        for (name, res, expected) in cases {
+            assert_close(name, res, expected);
+        }
+    }
+
+    #[test]
+    fn bessel_functions() {
+        assert_eq!(bessel_j(3, 0.), 0.);
+        let cases = [
+            ("besselj(0, 1)", bessel_j(0, 1.), 0.765_197_686_557_966_6),
+            ("besselj(1, 1)", bessel_j(1, 1.), 0.440_050_585_744_933_5),
+            ("besselj(0, 10)", bessel_j(0, 10.), -0.245_935_764_451_348_3),
+            ("besselj(5, 2)", bessel_j(5, 2.), 0.007_039_629_755_871_685),
+            ("besselj(-1, 1)", bessel_j(-1, 1.), -0.440_050_585_744_933_5),
+            ("bessely(0, 1)", bessel_y(0, 1.), 0.088_256_964_215_676_96),
+            ("bessely(1, 1)", bessel_y(1, 1.), -0.781_212_821_300_288_7),
+            ("bessely(0, 10)", bessel_y(0, 10.), 0.055_671_167_283_599_39),
+            ("bessely(1, 10)", bessel_y(1, 10.), 0.249_015_424_206_953_9),
+            ("bessely(2, 1)", bessel_y(2, 1.), -1.650_682_606_816_254_4),
+            // past BESSEL_ASYMPTOTIC
+            (
+                "besselj(0, 10000)",
+                bessel_j(0, 1e4),
+                -0.007_096_160_353_388_801,
+            ),
+            (
+                "besselj(3, -10000)",
+                bessel_j(3, -1e4),
+                0.003_644_611_999_592_164,
+            ),
+            (
+                "besselj(0, 1e12)",
+                bessel_j(0, 1e12),
+                1.016_712_505_004_068_2e-7,
+            ),
+            (
+                "bessely(0, 1e20)",
+                bessel_y(0, 1e20),
+                -7.950_681_982_425_45e-11,
+            ),
+        ];
+        for (name, res, expected) in cases {
+            assert_close(name, res, expected);
+        }
+    }
+
+    #[test]
+    fn lambertw_values() {
+        assert_eq!(lambertw(-1. / E), -1.);
+        let cases = [
+            ("lambertw(1)", lambertw(1.), 0.567_143_290_409_783_8),
+            ("lambertw(10)", lambertw(10.), 1.745_528_002_740_699_4),
+            ("lambertw(-0.3)", lambertw(-0.3), -0.489_402_227_180_214_9),
+        ];
+        for (name, res, expected) in cases {
+            assert_close(name, res, expected);
+        }
+    }
+}
@@ -0,0 +1,414 @@
+use std::f64::consts::{PI, SQRT_2};
+
+use crate::{
+    special::{beta_inc, erfc, lgamma},
+    value::Value,
+};
+
+// invnorm searches this interval, normcdf rounds to 0 or 1 outside it
+const INVNORM_BOUND: f64 = 40.;
+const BISECTION_STEPS: usize = 200;
+// below this probabilities are multiplied out directly, above it they go through lgamma to avoid overflow
+const DIRECT_LIMIT: u64 = 700;
+// cdf terms further than this many standard deviations from the mean are too small to count
+const CDF_TAIL: f64 = 40.;
+// cdfs of wider distributions are refused rather than summed for ever
+const MAX_CDF_TERMS: f64 = 1e6;
+
+fn numbers(func: &str, items: &[Value]) -> anyhow::Result<()> {
+    if items.is_empty() {
+        anyhow::bail!("{} of an empty list", func);
+    }
+    if let Some(item) = items.iter().find(|item| !item.is_number()) {
+        anyhow::bail!("{} expects a list of numbers but got {}", func, item);
+    }
+    Ok(())
+}
+
+fn sorted(func: &str, items: &[Value]) -> anyhow::Result<Vec<Value>> {
+    numbers(func, items)?;
+    if let Some(item) = items.iter().find(|item| item.is_complex()) {
+        anyhow::bail!("{} can not order the complex number {}", func, item);
+    }
+    let mut items = items.to_vec();
+    let mut err = None;
+    items.sort_by(|a, b| {
+        a.partial_cmp(b).unwrap_or_else(|| {
+            err.get_or_insert_with(|| anyhow::anyhow!("can not compare {} and {}", a, b));
+            std::cmp::Ordering::Equal
+        })
+    });
+    match err {
+        Some(err) => Err(err),
+        None => Ok(items),
+    }
+}
+
+// starts from the first item so units carry through, items must not be empty
+fn sum(items: &[Value]) -> anyhow::Result<Value> {
+    items[1..]
+        .iter()
+        .try_fold(items[0].clone(), |acc, x| acc.checked_add(x.clone()))
+}
+
+fn mean(func: &str, items: &[Value]) -> anyhow::Result<Value> {
+    numbers(func, items)?;
+    sum(items)?.checked_div(Value::from(items.len() as i64))
+}
+
+// sum of products of deviations from the means
+fn co_deviation(xs: &[Value], ys: &[Value]) -> anyhow::Result<Value> {
+    let (mx, my) = (mean("corr", xs)?, mean("corr", ys)?);
+    let products = xs.iter().zip(ys).map(|(x, y)| {
+        let dx = x.clone().checked_sub(mx.clone())?;
+        let dy = y.clone().checked_sub(my.clone())?;
+        Ok(dx * dy)
+    });
+    sum(&products.collect::<anyhow::Result<Vec<_>>>()?)
+}
+
+pub fn median(items: &[Value]) -> anyhow::Result<Value> {
+    let items = sorted("median", items)?;
+    let mid = items.len() / 2;
+    if items.len() % 2 == 1 {
+        return Ok(items[mid].clone());
+    }
+    items[mid - 1]
+        .clone()
+        .checked_add(items[mid].clone())?
+        .checked_div(Value::from(2))
+}
+
+// the most common value, the smallest one on ties
+pub fn mode(items: &[Value]) -> anyhow::Result<Value> {
+    let items = sorted("mode", items)?;
+    let (mut best, mut best_len) = (&items[0], 0);
+    for run in items.chunk_by(|a, b| a == b) {
+        if run.len() > best_len {
+            (best, best_len) = (&run[0], run.len());
+        }
+    }
+    Ok(best.clone())
+}
+
+// sample variance divides by n - 1, population variance by n
+pub fn variance(func: &str, items: &[Value], sample: bool) -> anyhow::Result<Value> {
+    numbers(func, items)?;
+    let len = items.len() as i64 - sample as i64;
+    if len == 0 {
+        anyhow::bail!("{} needs at least 2 values", func);
+    }
+    co_deviation(items, items)?.checked_div(Value::from(len))
+}
+
+// linear interpolation between the closest ranks, quantile(xs, 1/2) is the median
+pub fn quantile(items: &[Value], p: &Value) -> anyhow::Result<Value> {
+    let items = sorted("quantile", items)?;
+    if !p.is_number() || p.is_complex() || p.is_quantity() || !(0. ..=1.).contains(&p.to_f64()) {
+        anyhow::bail!(
+            "quantile expects a probability between 0 and 1 but got {}",
+            p
+        );
+    }
+
+    let rank = Value::from(items.len() as i64 - 1) * p.clone();
+    let lower = (rank.to_f64().floor() as usize).min(items.len() - 1);
+    let Some(upper) = items.get(lower + 1) else {
+        return Ok(items[lower].clone());
+    };
+    let frac = rank.checked_sub(Value::from(lower as i64))?;
+    let step = upper.clone().checked_sub(items[lower].clone())?;
+    items[lower].clone().checked_add(frac * step)
+}
+
+// Pearson correlation coefficient
+pub fn corr(xs: &[Value], ys: &[Value]) -> anyhow::Result<Value> {
+    if xs.len() != ys.len() {
+        anyhow::bail!(
+            "corr expects lists of the same length but got {} and {}",
+            xs.len(),
+            ys.len()
+        );
+    }
+    let spread = (co_deviation(xs, xs)? * co_deviation(ys, ys)?).sqrt()?;
+    // the spread of lists with units has units too
+    if spread.clone().sign().to_f64() == 0. {
+        anyhow::bail!("corr is undefined for constant lists");
+    }
+    co_deviation(xs, ys)?.checked_div(spread)
+}
+
+fn count(func: &str, value: f64) -> anyhow::Result<u64> {
+    if value < 0. || value.fract() != 0. {
+        anyhow::bail!("{} expects whole numbers but got {}", func, value);
+    }
+    Ok(value as u64)
+}
+
+fn probability(func: &str, p: f64) -> anyhow::Result<f64> {
+    if !(0. ..=1.).contains(&p) {
+        anyhow::bail!(
+            "{} expects a probability between 0 and 1 but got {}",
+            func,
+            p
+        );
+    }
+    Ok(p)
+}
+
+fn positive(func: &str, name: &str, value: f64) -> anyhow::Result<f64> {
+    if value <= 0. {
+        anyhow::bail!("{} expects a positive {} but got {}", func, name, value);
+    }
+    Ok(value)
+}
+
+fn norm_cdf(z: f64) -> f64 {
+    0.5 * erfc(-z / SQRT_2)
+}
+
+// bisection keeps this monotonic and exact to the last bit, speed does not matter here
+fn inv_norm_cdf(p: f64) -> f64 {
+    let (mut lo, mut hi) = (-INVNORM_BOUND, INVNORM_BOUND);
+    for _ in 0..BISECTION_STEPS {
+        let mid = (lo + hi) / 2.;
+        if mid == lo || mid == hi {
+            break;
+        }
+        if norm_cdf(mid) < p {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    (lo + hi) / 2.
+}
+
+// k ~ binomial(n, p)
+fn binom_pdf(n: u64, p: f64, k: u64) -> f64 {
+    if k > n {
+        return 0.;
+    }
+    match p {
+        0. => (k == 0) as u8 as f64,
+        1. => (k == n) as u8 as f64,
+        p if n <= DIRECT_LIMIT => {
+            let choose = (1..=k).fold(1., |acc, i| acc * (n - k + i) as f64 / i as f64);
+            choose * p.powi(k as i32) * (1. - p).powi((n - k) as i32)
+        }
+        p => {
+            let (n, k) = (n as f64, k as f64);
+            let ln_choose = lgamma(n + 1.) - lgamma(k + 1.) - lgamma(n - k + 1.);
+            (ln_choose + k * p.ln() + (n - k) * (1. - p).ln()).exp()
+        }
+    }
+}
+
+// k ~ poisson(lambda)
+fn poisson_pdf(lambda: f64, k: u64) -> f64 {
+    if lambda < DIRECT_LIMIT as f64 && k <= DIRECT_LIMIT {
+        return (1..=k).fold((-lambda).exp(), |acc, i| acc * lambda / i as f64);
+    }
+    let k = k as f64;
+    (k * lambda.ln() - lambda - lgamma(k + 1.)).exp()
+}
+
+// sums pdf from 0 to k, leaving out the tails that can not change the result
+fn discrete_cdf(
+    func: &str,
+    k: u64,
+    (mean, sd): (f64, f64),
+    pdf: impl Fn(u64) -> f64,
+) -> anyhow::Result<f64> {
+    let lo = (mean - CDF_TAIL * (sd + 1.)).max(0.);
+    let hi = mean + CDF_TAIL * (sd + 1.);
+    if k as f64 >= hi {
+        return Ok(1.);
+    }
+    if k as f64 - lo > MAX_CDF_TERMS {
+        anyhow::bail!("{} of {} would need too many terms", func, k);
+    }
+    Ok((lo as u64..=k).map(pdf).sum())
+}
+
+fn t_pdf(x: f64, df: f64) -> f64 {
+    let ln_norm = lgamma((df + 1.) / 2.) - lgamma(df / 2.) - 0.5 * (df * PI).ln();
+    (ln_norm - (df + 1.) / 2. * (x * x / df).ln_1p()).exp()
+}
+
+fn t_cdf(x: f64, df: f64) -> f64 {
+    let tail = 0.5 * beta_inc(df / 2., 0.5, df / (df + x * x));
+    if x > 0. {
+        1. - tail
+    } else {
+        tail
+    }
+}
+
+// the mean and standard deviation default to the standard normal distribution
+fn normal(func: &str, args: &[f64]) -> anyhow::Result<(f64, f64, f64)> {
+    match *args {
+        [x] => Ok((x, 0., 1.)),
+        [x, mu, sigma] => Ok((x, mu, positive(func, "standard deviation", sigma)?)),
+        _ => anyhow::bail!(
+            "{} takes 1 or 3 arguments but {} were given",
+            func,
+            args.len()
+        ),
+    }
+}
+
+pub fn normpdf(args: &[f64]) -> anyhow::Result<f64> {
+    let (x, mu, sigma) = normal("normpdf", args)?;
+    let z = (x - mu) / sigma;
+    Ok((-z * z / 2.).exp() / (sigma * (2. * PI).sqrt()))
+}
+
+pub fn normcdf(args: &[f64]) -> anyhow::Result<f64> {
+    let (x, mu, sigma) = normal("normcdf", args)?;
+    Ok(norm_cdf((x - mu) / sigma))
+}
+
+pub fn invnorm(args: &[f64]) -> anyhow::Result<f64> {
+    let (p, mu, sigma) = normal("invnorm", args)?;
+    Ok(match probability("invnorm", p)? {
+        0. => f64::NEG_INFINITY,
+        1. => f64::INFINITY,
+        p => mu + sigma * inv_norm_cdf(p),
+    })
+}
+
+pub fn binompdf(args: &[f64]) -> anyhow::Result<f64> {
+    let (n, p) = (
+        count("binompdf", args[0])?,
+        probability("binompdf", args[1])?,
+    );
+    Ok(binom_pdf(n, p, count("binompdf", args[2])?))
+}
+
+pub fn binomcdf(args: &[f64]) -> anyhow::Result<f64> {
+    let (n, p) = (
+        count("binomcdf", args[0])?,
+        probability("binomcdf", args[1])?,
+    );
+    let k = count("binomcdf", args[2])?.min(n);
+    let (mean, sd) = (n as f64 * p, (n as f64 * p * (1. - p)).sqrt());
+    discrete_cdf("binomcdf", k, (mean, sd), |i| binom_pdf(n, p, i))
+}
+
+pub fn poissonpdf(args: &[f64]) -> anyhow::Result<f64> {
+    let lambda = positive("poissonpdf", "rate", args[0])?;
+    Ok(poisson_pdf(lambda, count("poissonpdf", args[1])?))
+}
+
+pub fn poissoncdf(args: &[f64]) -> anyhow::Result<f64> {
+    let lambda = positive("poissoncdf", "rate", args[0])?;
+    let k = count("poissoncdf", args[1])?;
+    discrete_cdf("poissoncdf", k, (lambda, lambda.sqrt()), |i| {
+        poisson_pdf(lambda, i)
+    })
+}
+
+pub fn tpdf(args: &[f64]) -> anyhow::Result<f64> {
+    Ok(t_pdf(
+        args[0],
+        positive("tpdf", "degrees of freedom", args[1])?,
+    ))
+}
+
+pub fn tcdf(args: &[f64]) -> anyhow::Result<f64> {
+    Ok(t_cdf(
+        args[0],
+        positive("tcdf", "degrees of freedom", args[1])?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use num_complex::Complex64;
+
+    use super::*;
+
+    fn list(items: &[i64]) -> Vec<Value> {
+        items.iter().map(|&item| Value::from(item)).collect()
+    }
+
+    #[test]
+    fn summaries() {
+        let xs = list(&[2, 4, 4, 4, 5, 5, 7, 9]);
+        assert_eq!(median(&xs).unwrap(), Value::from(4.5));
+        assert_eq!(median(&list(&[3, 1, 2])).unwrap(), Value::from(2));
+        assert_eq!(mode(&xs).unwrap(), Value::from(4));
+        // ties go to the smallest
+        assert_eq!(mode(&list(&[3, 1, 3, 1])).unwrap(), Value::from(1));
+        assert_eq!(variance("pvar", &xs, false).unwrap(), Value::from(4));
+        assert_eq!(variance("var", &xs, true).unwrap().to_string(), "32/7");
+        let half = Value::from(1).checked_div(Value::from(2)).unwrap();
+        let quarter = Value::from(1).checked_div(Value::from(4)).unwrap();
+        assert_eq!(
+            quantile(&list(&[1, 2, 3, 4]), &quarter).unwrap(),
+            Value::from(1.75)
+        );
+        assert_eq!(quantile(&xs, &half).unwrap(), median(&xs).unwrap());
+        assert_eq!(
+            corr(&list(&[1, 2, 3]), &list(&[2, 4, 6])).unwrap(),
+            Value::from(1)
+        );
+        assert_eq!(
+            corr(&list(&[1, 2, 3]), &list(&[3, 1, 2])).unwrap(),
+            Value::from(-0.5)
+        );
+
+        assert!(median(&[]).is_err());
+        assert!(variance("var", &list(&[1]), true).is_err());
+        assert!(median(&[Value::from(1), Value::from_complex(Complex64::i())]).is_err());
+        assert!(corr(&list(&[1, 2]), &list(&[1, 2, 3])).is_err());
+        assert!(corr(&list(&[1, 1]), &list(&[1, 2])).is_err());
+        assert!(quantile(&xs, &Value::from(2)).is_err());
+    }
+
+    // reference values from statistical tables
+    #[test]
+    fn distributions() {
+        assert_eq!(normcdf(&[0.]).unwrap(), 0.5);
+        assert_eq!(binompdf(&[10., 0.5, 3.]).unwrap(), 0.117_187_5);
+        assert_eq!(binomcdf(&[10., 0.5, 10.]).unwrap(), 1.);
+        assert_eq!(binomcdf(&[10., 0.5, 1e12]).unwrap(), 1.);
+        assert_eq!(poissoncdf(&[2., 1e12]).unwrap(), 1.);
+        assert_eq!(invnorm(&[0.5, 100., 15.]).unwrap(), 100.);
+        assert_eq!(tcdf(&[0., 3.]).unwrap(), 0.5);
+
+        let cases = [
+            ("normpdf(0)", normpdf(&[0.]), 0.398_942_280_401_432_7),
+            ("normcdf(1.96)", normcdf(&[1.96]), 0.975_002_104_851_779_5),
+            (
+                "normcdf(110, 100, 15)",
+                normcdf(&[110., 100., 15.]),
+                0.747_507_462_453_077,
+            ),
+            ("invnorm(0.975)", invnorm(&[0.975]), 1.959_963_984_540_054),
+            (
+                "poissonpdf(3, 2)",
+                poissonpdf(&[3., 2.]),
+                0.224_041_807_655_387_8,
+            ),
+            (
+                "poissoncdf(3, 2)",
+                poissoncdf(&[3., 2.]),
+                0.423_190_081_126_843_6,
+            ),
+            ("tpdf(0, 1)", tpdf(&[0., 1.]), 1. / PI),
+            ("tcdf(2, 5)", tcdf(&[2., 5.]), 0.949_030_260_585_070_9),
+            ("tcdf(-1, 1)", tcdf(&[-1., 1.]), 0.25),
+        ];
+        for (name, res, expected) in cases {
+            let res = res.unwrap();
+            assert!((res - expected).abs() < 1e-12, "{} = {}", name, res);
+        }
+
+        assert!(invnorm(&[1.5]).is_err());
+        assert!(binompdf(&[10., 0.5, 1.5]).is_err());
+        assert!(poissoncdf(&[1e14, 1e14]).is_err());
+        assert!(normpdf(&[0., 0., -1.]).is_err());
+    }
+}
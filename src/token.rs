@@ -2,6 +2,7 @@
 pub struct Token {
     pub value: String,
     pub token: TokenType,
+    // index of the first character, for pointing at errors
     pub start: usize,
 }
 
@@ -26,6 +27,34 @@ pub enum TokenType {
     LParen,
     RParen,
     Ident,
-    EOI,
+    Eoi,
     Assign,
+    Comma,
+    Unit,
+    To,
+    Prime,
+    Less,
+    LessEq,
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEq,
+    And,
+    Or,
+    Not,
+    If,
+    Then,
+    Else,
+    Arrow,
+    LBracket,
+    RBracket,
+    Range,
+    Semicolon,
+    BitAnd,
+    BitOr,
+    Xor,
+    ShiftLeft,
+    ShiftRight,
+    Tilde,
+    Sqrt,
 }
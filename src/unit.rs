@@ -0,0 +1,260 @@
+use std::{f64::consts, fmt::Display};
+
+// exponents of kg, m, s, A, K, mol and cd
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Dimension([i32; 7]);
+
+const BASE_SYMBOLS: [&str; 7] = ["kg", "m", "s", "A", "K", "mol", "cd"];
+
+impl Dimension {
+    const fn new(kg: i32, m: i32, s: i32, a: i32, k: i32, mol: i32, cd: i32) -> Self {
+        Self([kg, m, s, a, k, mol, cd])
+    }
+
+    pub fn is_dimensionless(&self) -> bool {
+        self.0.iter().all(|exp| *exp == 0)
+    }
+
+    pub fn mul(self, rhs: Dimension) -> Dimension {
+        let mut res = self;
+        for (exp, rhs) in res.0.iter_mut().zip(rhs.0) {
+            *exp += rhs;
+        }
+        res
+    }
+
+    pub fn div(self, rhs: Dimension) -> Dimension {
+        self.mul(rhs.pow(-1))
+    }
+
+    pub fn pow(self, exp: i32) -> Dimension {
+        Dimension(self.0.map(|base| base * exp))
+    }
+
+    // only succeeds when every exponent is divisible by the root
+    pub fn root(self, root: i32) -> Option<Dimension> {
+        self.0
+            .iter()
+            .all(|exp| exp % root == 0)
+            .then(|| Dimension(self.0.map(|exp| exp / root)))
+    }
+}
+
+impl Display for Dimension {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_dimensionless() {
+            return write!(f, "1");
+        }
+
+        let part = |symbol: &str, exp: i32| {
+            if exp == 1 {
+                symbol.to_owned()
+            } else {
+                format!("{}^{}", symbol, exp)
+            }
+        };
+
+        let positive: Vec<String> = BASE_SYMBOLS
+            .iter()
+            .zip(self.0)
+            .filter(|(_, exp)| *exp > 0)
+            .map(|(symbol, exp)| part(symbol, exp))
+            .collect();
+        let negative = BASE_SYMBOLS.iter().zip(self.0).filter(|(_, exp)| *exp < 0);
+
+        if positive.is_empty() {
+            let negative: Vec<String> = negative.map(|(symbol, exp)| part(symbol, exp)).collect();
+            return write!(f, "{}", negative.join("*"));
+        }
+
+        write!(f, "{}", positive.join("*"))?;
+        for (symbol, exp) in negative {
+            write!(f, "/{}", part(symbol, -exp))?;
+        }
+        Ok(())
+    }
+}
+
+struct UnitDef {
+    name: &'static str,
+    factor: f64,
+    dimension: Dimension,
+    prefixable: bool,
+}
+
+const fn unit(name: &'static str, factor: f64, dimension: Dimension, prefixable: bool) -> UnitDef {
+    UnitDef {
+        name,
+        factor,
+        dimension,
+        prefixable,
+    }
+}
+
+const MASS: Dimension = Dimension::new(1, 0, 0, 0, 0, 0, 0);
+const LENGTH: Dimension = Dimension::new(0, 1, 0, 0, 0, 0, 0);
+const TIME: Dimension = Dimension::new(0, 0, 1, 0, 0, 0, 0);
+const CURRENT: Dimension = Dimension::new(0, 0, 0, 1, 0, 0, 0);
+const TEMPERATURE: Dimension = Dimension::new(0, 0, 0, 0, 1, 0, 0);
+const AMOUNT: Dimension = Dimension::new(0, 0, 0, 0, 0, 1, 0);
+const LUMINOSITY: Dimension = Dimension::new(0, 0, 0, 0, 0, 0, 1);
+const AREA: Dimension = Dimension::new(0, 2, 0, 0, 0, 0, 0);
+const VOLUME: Dimension = Dimension::new(0, 3, 0, 0, 0, 0, 0);
+const FREQUENCY: Dimension = Dimension::new(0, 0, -1, 0, 0, 0, 0);
+const SPEED: Dimension = Dimension::new(0, 1, -1, 0, 0, 0, 0);
+const FORCE: Dimension = Dimension::new(1, 1, -2, 0, 0, 0, 0);
+const PRESSURE: Dimension = Dimension::new(1, -1, -2, 0, 0, 0, 0);
+const ENERGY: Dimension = Dimension::new(1, 2, -2, 0, 0, 0, 0);
+const POWER: Dimension = Dimension::new(1, 2, -3, 0, 0, 0, 0);
+const CHARGE: Dimension = Dimension::new(0, 0, 1, 1, 0, 0, 0);
+const VOLTAGE: Dimension = Dimension::new(1, 2, -3, -1, 0, 0, 0);
+const RESISTANCE: Dimension = Dimension::new(1, 2, -3, -2, 0, 0, 0);
+const CAPACITANCE: Dimension = Dimension::new(-1, -2, 4, 2, 0, 0, 0);
+const MAGNETIC_FLUX_DENSITY: Dimension = Dimension::new(1, 0, -2, -1, 0, 0, 0);
+// angles are ratios, the radian is the base
+const ANGLE: Dimension = Dimension::new(0, 0, 0, 0, 0, 0, 0);
+
+// factors convert a value in the unit to SI base units
+const UNITS: &[UnitDef] = &[
+    unit("g", 1e-3, MASS, true),
+    unit("t", 1e3, MASS, false),
+    unit("lb", 0.45359237, MASS, false),
+    unit("oz", 0.028349523125, MASS, false),
+    unit("m", 1., LENGTH, true),
+    unit("in", 0.0254, LENGTH, false),
+    unit("ft", 0.3048, LENGTH, false),
+    unit("yd", 0.9144, LENGTH, false),
+    unit("mi", 1609.344, LENGTH, false),
+    unit("nmi", 1852., LENGTH, false),
+    unit("au", 149597870700., LENGTH, false),
+    unit("ly", 9460730472580800., LENGTH, false),
+    unit("s", 1., TIME, true),
+    unit("min", 60., TIME, false),
+    unit("h", 3600., TIME, false),
+    unit("day", 86400., TIME, false),
+    unit("week", 604800., TIME, false),
+    unit("yr", 31557600., TIME, false),
+    unit("A", 1., CURRENT, true),
+    unit("K", 1., TEMPERATURE, true),
+    unit("mol", 1., AMOUNT, true),
+    unit("cd", 1., LUMINOSITY, true),
+    unit("ha", 1e4, AREA, false),
+    unit("acre", 4046.8564224, AREA, false),
+    unit("L", 1e-3, VOLUME, true),
+    unit("gal", 3.785411784e-3, VOLUME, false),
+    unit("Hz", 1., FREQUENCY, true),
+    unit("mph", 0.44704, SPEED, false),
+    unit("kn", 1852. / 3600., SPEED, false),
+    unit("N", 1., FORCE, true),
+    unit("lbf", 4.4482216152605, FORCE, false),
+    unit("Pa", 1., PRESSURE, true),
+    unit("bar", 1e5, PRESSURE, true),
+    unit("atm", 101325., PRESSURE, false),
+    unit("psi", 6894.757293168361, PRESSURE, false),
+    unit("J", 1., ENERGY, true),
+    unit("cal", 4.184, ENERGY, true),
+    unit("eV", 1.602176634e-19, ENERGY, true),
+    unit("Wh", 3600., ENERGY, true),
+    unit("W", 1., POWER, true),
+    unit("hp", 745.6998715822702, POWER, false),
+    unit("C", 1., CHARGE, true),
+    unit("V", 1., VOLTAGE, true),
+    unit("ohm", 1., RESISTANCE, true),
+    unit("F", 1., CAPACITANCE, true),
+    unit("T", 1., MAGNETIC_FLUX_DENSITY, true),
+    unit("rad", 1., ANGLE, false),
+    unit("deg", consts::PI / 180., ANGLE, false),
+    unit("grad", consts::PI / 200., ANGLE, false),
+];
+
+const PREFIXES: &[(&str, f64)] = &[
+    ("Y", 1e24),
+    ("Z", 1e21),
+    ("E", 1e18),
+    ("P", 1e15),
+    ("T", 1e12),
+    ("G", 1e9),
+    ("M", 1e6),
+    ("k", 1e3),
+    ("h", 1e2),
+    ("da", 1e1),
+    ("d", 1e-1),
+    ("c", 1e-2),
+    ("m", 1e-3),
+    ("u", 1e-6),
+    ("µ", 1e-6),
+    ("n", 1e-9),
+    ("p", 1e-12),
+    ("f", 1e-15),
+    ("a", 1e-18),
+];
+
+// exact names win over prefixed ones so `min` is minutes and `mm` is millimetres
+fn lookup(name: &str) -> Option<(f64, Dimension)> {
+    if let Some(unit) = UNITS.iter().find(|unit| unit.name == name) {
+        return Some((unit.factor, unit.dimension));
+    }
+
+    PREFIXES.iter().find_map(|(prefix, scale)| {
+        let rest = name.strip_prefix(prefix)?;
+        let unit = UNITS
+            .iter()
+            .find(|unit| unit.prefixable && unit.name == rest)?;
+        Some((scale * unit.factor, unit.dimension))
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unit {
+    pub name: String,
+    pub factor: f64,
+    pub dimension: Dimension,
+}
+
+impl Unit {
+    // the coherent SI unit for a dimension, such as `kg*m/s^2`
+    pub fn base(dimension: Dimension) -> Self {
+        Self {
+            name: dimension.to_string(),
+            factor: 1.,
+            dimension,
+        }
+    }
+
+    // parses compound units such as `km/h` or `kg*m^2/s^2`
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let mut factor = 1.;
+        let mut dimension = Dimension::default();
+
+        let mut rest = text;
+        let mut divide = false;
+        loop {
+            let end = rest.find(['*', '/']).unwrap_or(rest.len());
+            let (part, tail) = rest.split_at(end);
+
+            let (name, exp) = match part.split_once('^') {
+                Some((name, exp)) => (name, exp.parse::<i32>()?),
+                None => (part, 1),
+            };
+            let exp = if divide { -exp } else { exp };
+
+            let Some((part_factor, part_dimension)) = lookup(name) else {
+                anyhow::bail!("unknown unit: {}", name);
+            };
+            factor *= part_factor.powi(exp);
+            dimension = dimension.mul(part_dimension.pow(exp));
+
+            let Some(op) = tail.chars().next() else {
+                break;
+            };
+            divide = op == '/';
+            rest = &tail[1..];
+        }
+
+        Ok(Self {
+            name: text.to_owned(),
+            factor,
+            dimension,
+        })
+    }
+}
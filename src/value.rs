@@ -0,0 +1,627 @@
+use std::{
+    cmp::Ordering,
+    fmt::Display,
+    ops::{Mul, Neg},
+    rc::Rc,
+};
+
+use num_bigint::BigInt;
+use num_complex::Complex64;
+use num_rational::BigRational;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+
+use crate::{
+    ast::fmt_list,
+    matrix::Matrix,
+    scope::Closure,
+    unit::{Dimension, Unit},
+};
+
+// results bigger than this many bits are computed as floats instead
+const MAX_EXACT_BITS: u64 = 1 << 16;
+// about the same size as MAX_EXACT_BITS, for number literals
+const MAX_EXACT_DIGITS: u64 = 20_000;
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Int(BigInt),
+    Rational(BigRational),
+    Float(f64),
+    Complex(Complex64),
+    // magnitude in the given unit
+    Quantity(Box<Value>, Unit),
+    Function(Rc<Closure>),
+    List(Vec<Value>),
+    Matrix(Matrix),
+}
+
+// both sides promoted to the same representation
+enum Pair {
+    Int(BigInt, BigInt),
+    Rational(BigRational, BigRational),
+    Float(f64, f64),
+    Complex(Complex64, Complex64),
+}
+
+impl Value {
+    pub fn from_rational(rational: BigRational) -> Self {
+        if rational.is_integer() {
+            Value::Int(rational.to_integer())
+        } else {
+            Value::Rational(rational)
+        }
+    }
+
+    // parses a decimal literal such as `12`, `.25` or `6.022e23` exactly
+    pub fn from_decimal_str(text: &str) -> anyhow::Result<Self> {
+        let (mantissa, exponent) = text.split_once(['e', 'E']).unwrap_or((text, "0"));
+        let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        let digits = format!("{}{}", whole, fraction);
+        if digits.is_empty() {
+            anyhow::bail!("invalid number: {}", text);
+        }
+
+        // exponents too big to write out exactly give infinity or 0 like other huge results
+        let exponent = exponent
+            .parse::<i64>()
+            .unwrap_or(i64::MAX)
+            .saturating_sub(fraction.len() as i64);
+        if exponent.unsigned_abs() > MAX_EXACT_DIGITS {
+            return Ok(Value::Float(text.parse()?));
+        }
+
+        let numer: BigInt = digits.parse()?;
+        let scale = BigInt::from(10).pow(exponent.unsigned_abs() as u32);
+        Ok(Value::from_rational(if exponent < 0 {
+            BigRational::new(numer, scale)
+        } else {
+            BigRational::from_integer(numer * scale)
+        }))
+    }
+
+    // complex numbers with no imaginary part are stored as plain floats
+    pub fn from_complex(complex: Complex64) -> Self {
+        if complex.im == 0. {
+            Value::Float(complex.re)
+        } else {
+            Value::Complex(complex)
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Value::Int(int) => int.to_f64().unwrap_or(f64::NAN),
+            Value::Rational(rational) => rational.to_f64().unwrap_or(f64::NAN),
+            Value::Float(float) => *float,
+            Value::Complex(_)
+            | Value::Quantity(..)
+            | Value::Function(_)
+            | Value::List(_)
+            | Value::Matrix(_) => f64::NAN,
+        }
+    }
+
+    pub fn to_complex(&self) -> Complex64 {
+        match self {
+            Value::Complex(complex) => *complex,
+            real => Complex64::new(real.to_f64(), 0.),
+        }
+    }
+
+    fn to_rational(&self) -> Option<BigRational> {
+        match self {
+            Value::Int(int) => Some(BigRational::from_integer(int.clone())),
+            Value::Rational(rational) => Some(rational.clone()),
+            Value::Float(_)
+            | Value::Complex(_)
+            | Value::Quantity(..)
+            | Value::Function(_)
+            | Value::List(_)
+            | Value::Matrix(_) => None,
+        }
+    }
+
+    pub fn is_complex(&self) -> bool {
+        matches!(self, Value::Complex(_))
+    }
+
+    fn promote(self, rhs: Value) -> Pair {
+        match (self, rhs) {
+            (Value::Int(lhs), Value::Int(rhs)) => Pair::Int(lhs, rhs),
+            (lhs, rhs) if lhs.is_complex() || rhs.is_complex() => {
+                Pair::Complex(lhs.to_complex(), rhs.to_complex())
+            }
+            (lhs, rhs) => match (lhs.to_rational(), rhs.to_rational()) {
+                (Some(lhs), Some(rhs)) => Pair::Rational(lhs, rhs),
+                _ => Pair::Float(lhs.to_f64(), rhs.to_f64()),
+            },
+        }
+    }
+
+    // uses the real function inside its domain and the complex one everywhere else
+    pub fn map_float(
+        &self,
+        in_domain: fn(f64) -> bool,
+        real: fn(f64) -> f64,
+        complex: fn(Complex64) -> Complex64,
+    ) -> Value {
+        match self {
+            Value::Complex(z) => Value::from_complex(complex(*z)),
+            x if in_domain(x.to_f64()) => Value::Float(real(x.to_f64())),
+            x => Value::from_complex(complex(x.to_complex())),
+        }
+    }
+
+    pub fn re(&self) -> Value {
+        match self {
+            Value::Complex(complex) => Value::Float(complex.re),
+            real => real.clone(),
+        }
+    }
+
+    pub fn im(&self) -> Value {
+        match self {
+            Value::Complex(complex) => Value::Float(complex.im),
+            _ => Value::default(),
+        }
+    }
+
+    pub fn arg(&self) -> Value {
+        Value::Float(self.to_complex().arg())
+    }
+
+    pub fn conj(&self) -> Value {
+        match self {
+            Value::Complex(complex) => Value::Complex(complex.conj()),
+            real => real.clone(),
+        }
+    }
+
+    pub fn is_quantity(&self) -> bool {
+        matches!(self, Value::Quantity(..))
+    }
+
+    // a plain number or quantity rather than a function or collection
+    pub fn is_number(&self) -> bool {
+        !self.is_function() && !self.is_list() && !self.is_matrix()
+    }
+
+    pub fn is_function(&self) -> bool {
+        matches!(self, Value::Function(_))
+    }
+
+    pub fn is_list(&self) -> bool {
+        matches!(self, Value::List(_))
+    }
+
+    pub fn is_matrix(&self) -> bool {
+        matches!(self, Value::Matrix(_))
+    }
+
+    // conditions are 1 or 0, any other nonzero number also counts as true
+    pub fn from_bool(b: bool) -> Self {
+        Value::from(b as i64)
+    }
+
+    pub fn is_true(&self) -> anyhow::Result<bool> {
+        if self.is_quantity() || !self.is_number() {
+            anyhow::bail!("expected a number as a condition but got {}", self);
+        }
+        Ok(*self != Value::default())
+    }
+
+    // splits a value into its magnitude in SI base units and its dimension
+    fn into_si(self) -> (Value, Dimension) {
+        match self {
+            Value::Quantity(magnitude, unit) if unit.factor == 1. => (*magnitude, unit.dimension),
+            Value::Quantity(magnitude, unit) => (
+                mul_numbers(*magnitude, Value::Float(unit.factor)),
+                unit.dimension,
+            ),
+            value => (value, Dimension::default()),
+        }
+    }
+
+    fn from_si(magnitude: Value, dimension: Dimension) -> Value {
+        if dimension.is_dimensionless() {
+            magnitude
+        } else {
+            Value::Quantity(Box::new(magnitude), Unit::base(dimension))
+        }
+    }
+
+    // quantities in the same unit keep it, anything else is added in SI base units
+    fn add_or_sub(self, rhs: Value, op: fn(Value, Value) -> Value) -> anyhow::Result<Value> {
+        match (self, rhs) {
+            (Value::Quantity(lhs, lhs_unit), Value::Quantity(rhs, rhs_unit))
+                if lhs_unit.name == rhs_unit.name =>
+            {
+                Ok(Value::Quantity(Box::new(op(*lhs, *rhs)), lhs_unit))
+            }
+            (lhs, rhs) if lhs.is_quantity() || rhs.is_quantity() => {
+                let (lhs, lhs_dimension) = lhs.into_si();
+                let (rhs, rhs_dimension) = rhs.into_si();
+                if lhs_dimension != rhs_dimension {
+                    anyhow::bail!(
+                        "incompatible dimensions: {} and {}",
+                        lhs_dimension,
+                        rhs_dimension
+                    );
+                }
+                Ok(Value::from_si(op(lhs, rhs), lhs_dimension))
+            }
+            (lhs, rhs) => Ok(op(lhs, rhs)),
+        }
+    }
+
+    pub fn checked_add(self, rhs: Value) -> anyhow::Result<Value> {
+        self.add_or_sub(rhs, add_numbers)
+    }
+
+    pub fn checked_sub(self, rhs: Value) -> anyhow::Result<Value> {
+        self.add_or_sub(rhs, sub_numbers)
+    }
+
+    pub fn checked_div(self, rhs: Value) -> anyhow::Result<Value> {
+        match (self, rhs) {
+            (Value::Quantity(lhs, unit), rhs) if !rhs.is_quantity() => {
+                Ok(Value::Quantity(Box::new(div_numbers(*lhs, rhs)?), unit))
+            }
+            (lhs, rhs) if lhs.is_quantity() || rhs.is_quantity() => {
+                let (lhs, lhs_dimension) = lhs.into_si();
+                let (rhs, rhs_dimension) = rhs.into_si();
+                Ok(Value::from_si(
+                    div_numbers(lhs, rhs)?,
+                    lhs_dimension.div(rhs_dimension),
+                ))
+            }
+            (lhs, rhs) => div_numbers(lhs, rhs),
+        }
+    }
+
+    pub fn pow(self, rhs: Value) -> anyhow::Result<Value> {
+        if rhs.is_quantity() {
+            anyhow::bail!("exponents must be dimensionless");
+        }
+
+        if self.is_quantity() {
+            let (magnitude, dimension) = self.into_si();
+            let exp = rhs
+                .to_rational()
+                .and_then(|exp| Some((exp.numer().to_i32()?, exp.denom().to_i32()?)));
+            let Some(dimension) = exp.and_then(|(numer, denom)| dimension.pow(numer).root(denom))
+            else {
+                anyhow::bail!("can not raise {} to the power of {}", dimension, rhs);
+            };
+            return Ok(Value::from_si(pow_numbers(magnitude, rhs)?, dimension));
+        }
+
+        pow_numbers(self, rhs)
+    }
+
+    pub fn convert(self, unit: &Unit) -> anyhow::Result<Value> {
+        if let Value::List(items) = self {
+            return items
+                .into_iter()
+                .map(|item| item.convert(unit))
+                .collect::<anyhow::Result<_>>()
+                .map(Value::List);
+        }
+        if let Value::Matrix(matrix) = self {
+            return matrix.try_map(|item| item.convert(unit)).map(Value::Matrix);
+        }
+
+        let (magnitude, dimension) = self.into_si();
+        if dimension != unit.dimension {
+            anyhow::bail!("can not convert {} to {}", dimension, unit.name);
+        }
+
+        let magnitude = if unit.factor == 1. {
+            magnitude
+        } else {
+            div_numbers(magnitude, Value::Float(unit.factor))?
+        };
+        Ok(Value::Quantity(Box::new(magnitude), unit.clone()))
+    }
+
+    pub fn abs(self) -> Value {
+        match self {
+            Value::Int(int) => Value::Int(int.abs()),
+            Value::Rational(rational) => Value::Rational(rational.abs()),
+            Value::Float(float) => Value::Float(float.abs()),
+            Value::Complex(complex) => Value::Float(complex.norm()),
+            Value::Quantity(magnitude, unit) => Value::Quantity(Box::new(magnitude.abs()), unit),
+            Value::Function(_) => self,
+            Value::List(items) => Value::List(items.into_iter().map(Value::abs).collect()),
+            Value::Matrix(matrix) => Value::Matrix(matrix.map(Value::abs)),
+        }
+    }
+
+    // exact when the value is a perfect square
+    pub fn sqrt(self) -> anyhow::Result<Value> {
+        if self.is_quantity() {
+            let half = BigRational::new(BigInt::one(), BigInt::from(2));
+            return self.pow(Value::Rational(half));
+        }
+
+        if let Some(rational) = self.to_rational() {
+            if !rational.is_negative() {
+                let numer = rational.numer().sqrt();
+                let denom = rational.denom().sqrt();
+                if &numer * &numer == *rational.numer() && &denom * &denom == *rational.denom() {
+                    return Ok(Value::from_rational(BigRational::new(numer, denom)));
+                }
+            }
+        }
+        Ok(self.map_float(|x| x >= 0., f64::sqrt, |z| z.sqrt()))
+    }
+
+    // exact when the value is a perfect cube, real for negative numbers
+    pub fn cbrt(self) -> anyhow::Result<Value> {
+        if self.is_quantity() {
+            let third = BigRational::new(BigInt::one(), BigInt::from(3));
+            return self.pow(Value::Rational(third));
+        }
+
+        if let Some(rational) = self.to_rational() {
+            let numer = rational.numer().cbrt();
+            let denom = rational.denom().cbrt();
+            if numer.pow(3) == *rational.numer() && denom.pow(3) == *rational.denom() {
+                return Ok(Value::from_rational(BigRational::new(numer, denom)));
+            }
+        }
+        Ok(self.map_float(|_| true, f64::cbrt, |z| z.powf(1. / 3.)))
+    }
+
+    // floor, ceil, round and trunc, exact for fractions and keeping the unit of quantities
+    pub fn round_with(
+        self,
+        exact: fn(&BigRational) -> BigRational,
+        float: fn(f64) -> f64,
+    ) -> anyhow::Result<Value> {
+        Ok(match self {
+            Value::Quantity(magnitude, unit) => {
+                Value::Quantity(Box::new(magnitude.round_with(exact, float)?), unit)
+            }
+            Value::Complex(complex) => {
+                anyhow::bail!("can not round the complex number {}", complex)
+            }
+            Value::Float(float_value) => Value::Float(float(float_value)),
+            value => match value.to_rational() {
+                Some(rational) => Value::from_rational(exact(&rational)),
+                None => anyhow::bail!("can not round {}", value),
+            },
+        })
+    }
+
+    // -1, 0 or 1, and z / |z| for complex numbers
+    pub fn sign(self) -> Value {
+        match self {
+            Value::Quantity(magnitude, _) => magnitude.sign(),
+            Value::Float(0.) => Value::Float(0.),
+            Value::Float(float) => Value::Float(float.signum()),
+            Value::Complex(complex) => Value::from_complex(complex / complex.norm()),
+            value => Value::from(match value.partial_cmp(&Value::default()) {
+                Some(Ordering::Less) => -1,
+                Some(Ordering::Greater) => 1,
+                _ => 0,
+            }),
+        }
+    }
+}
+
+fn add_numbers(lhs: Value, rhs: Value) -> Value {
+    match lhs.promote(rhs) {
+        Pair::Int(lhs, rhs) => Value::Int(lhs + rhs),
+        Pair::Rational(lhs, rhs) => Value::from_rational(lhs + rhs),
+        Pair::Float(lhs, rhs) => Value::Float(lhs + rhs),
+        Pair::Complex(lhs, rhs) => Value::from_complex(lhs + rhs),
+    }
+}
+
+fn sub_numbers(lhs: Value, rhs: Value) -> Value {
+    match lhs.promote(rhs) {
+        Pair::Int(lhs, rhs) => Value::Int(lhs - rhs),
+        Pair::Rational(lhs, rhs) => Value::from_rational(lhs - rhs),
+        Pair::Float(lhs, rhs) => Value::Float(lhs - rhs),
+        Pair::Complex(lhs, rhs) => Value::from_complex(lhs - rhs),
+    }
+}
+
+fn mul_numbers(lhs: Value, rhs: Value) -> Value {
+    match lhs.promote(rhs) {
+        Pair::Int(lhs, rhs) => Value::Int(lhs * rhs),
+        Pair::Rational(lhs, rhs) => Value::from_rational(lhs * rhs),
+        Pair::Float(lhs, rhs) => Value::Float(lhs * rhs),
+        Pair::Complex(lhs, rhs) => Value::from_complex(lhs * rhs),
+    }
+}
+
+fn div_numbers(lhs: Value, rhs: Value) -> anyhow::Result<Value> {
+    Ok(match lhs.promote(rhs) {
+        Pair::Int(lhs, rhs) => {
+            if rhs.is_zero() {
+                anyhow::bail!("division by zero");
+            }
+            Value::from_rational(BigRational::new(lhs, rhs))
+        }
+        Pair::Rational(lhs, rhs) => {
+            if rhs.is_zero() {
+                anyhow::bail!("division by zero");
+            }
+            Value::from_rational(lhs / rhs)
+        }
+        Pair::Float(lhs, rhs) => Value::Float(lhs / rhs),
+        Pair::Complex(lhs, rhs) => Value::from_complex(lhs / rhs),
+    })
+}
+
+fn pow_numbers(lhs: Value, rhs: Value) -> anyhow::Result<Value> {
+    if let (Some(base), Value::Int(exp)) = (lhs.to_rational(), &rhs) {
+        let bits = base.numer().bits().max(base.denom().bits());
+        if let Some(exp) = exp.to_i32() {
+            if bits.saturating_mul(exp.unsigned_abs() as u64) <= MAX_EXACT_BITS {
+                if exp < 0 && base.is_zero() {
+                    anyhow::bail!("division by zero");
+                }
+                return Ok(Value::from_rational(base.pow(exp)));
+            }
+        }
+    }
+
+    if let (Value::Complex(base), Value::Int(exp)) = (&lhs, &rhs) {
+        if let Some(exp) = exp.to_i32() {
+            return Ok(Value::from_complex(base.powi(exp)));
+        }
+    }
+
+    let (base, exp) = (lhs.to_f64(), rhs.to_f64());
+    if lhs.is_complex() || rhs.is_complex() || (base < 0. && exp.fract() != 0.) {
+        return Ok(Value::from_complex(lhs.to_complex().powc(rhs.to_complex())));
+    }
+    Ok(Value::Float(base.powf(exp)))
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Value::Int(value.into())
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Float(value)
+    }
+}
+
+impl Default for Value {
+    fn default() -> Self {
+        Value::Int(BigInt::zero())
+    }
+}
+
+// a plain number scaling a quantity keeps its unit
+impl Mul for Value {
+    type Output = Value;
+
+    fn mul(self, rhs: Value) -> Value {
+        match (self, rhs) {
+            (Value::Quantity(lhs, unit), rhs) if !rhs.is_quantity() => {
+                Value::Quantity(Box::new(mul_numbers(*lhs, rhs)), unit)
+            }
+            (lhs, Value::Quantity(rhs, unit)) if !lhs.is_quantity() => {
+                Value::Quantity(Box::new(mul_numbers(lhs, *rhs)), unit)
+            }
+            (lhs, rhs) if lhs.is_quantity() || rhs.is_quantity() => {
+                let (lhs, lhs_dimension) = lhs.into_si();
+                let (rhs, rhs_dimension) = rhs.into_si();
+                Value::from_si(mul_numbers(lhs, rhs), lhs_dimension.mul(rhs_dimension))
+            }
+            (lhs, rhs) => mul_numbers(lhs, rhs),
+        }
+    }
+}
+
+impl Neg for Value {
+    type Output = Value;
+
+    fn neg(self) -> Value {
+        match self {
+            Value::Int(int) => Value::Int(-int),
+            Value::Rational(rational) => Value::Rational(-rational),
+            Value::Float(float) => Value::Float(-float),
+            Value::Complex(complex) => Value::Complex(-complex),
+            Value::Quantity(magnitude, unit) => Value::Quantity(Box::new(-*magnitude), unit),
+            Value::Function(_) => self,
+            Value::List(items) => Value::List(items.into_iter().map(Neg::neg).collect()),
+            Value::Matrix(matrix) => Value::Matrix(matrix.map(Neg::neg)),
+        }
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            // lists and matrices are only equal or not
+            (Value::List(lhs), Value::List(rhs)) => {
+                return (lhs.len() == rhs.len() && lhs.iter().zip(rhs).all(|(a, b)| a == b))
+                    .then_some(Ordering::Equal);
+            }
+            (Value::Matrix(lhs), Value::Matrix(rhs)) => {
+                return (lhs == rhs).then_some(Ordering::Equal);
+            }
+            (lhs, rhs) if !lhs.is_number() || !rhs.is_number() => return None,
+            _ => {}
+        }
+        if self.is_quantity() || other.is_quantity() {
+            let (lhs, lhs_dimension) = self.clone().into_si();
+            let (rhs, rhs_dimension) = other.clone().into_si();
+            return (lhs_dimension == rhs_dimension)
+                .then(|| lhs.partial_cmp(&rhs))
+                .flatten();
+        }
+
+        match self.clone().promote(other.clone()) {
+            Pair::Int(lhs, rhs) => lhs.partial_cmp(&rhs),
+            Pair::Rational(lhs, rhs) => lhs.partial_cmp(&rhs),
+            Pair::Float(lhs, rhs) => lhs.partial_cmp(&rhs),
+            // complex numbers can only be compared for equality
+            Pair::Complex(lhs, rhs) => (lhs == rhs).then_some(Ordering::Equal),
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Int(int) => write!(f, "{}", int),
+            Value::Rational(rational) => {
+                // fractions with a terminating decimal expansion are printed as decimals
+                let mut denom = rational.denom().clone();
+                let mut places = 0;
+                for factor in [2u32, 5] {
+                    let mut count = 0;
+                    while (&denom % factor).is_zero() {
+                        denom /= factor;
+                        count += 1;
+                    }
+                    places = places.max(count);
+                }
+
+                if !denom.is_one() {
+                    return write!(f, "{}/{}", rational.numer(), rational.denom());
+                }
+
+                let scaled = (rational * BigRational::from_integer(BigInt::from(10).pow(places)))
+                    .to_integer();
+                let sign = if scaled.is_negative() { "-" } else { "" };
+                let digits = format!("{:0>width$}", scaled.abs(), width = places as usize + 1);
+                let (whole, fraction) = digits.split_at(digits.len() - places as usize);
+                write!(f, "{}{}.{}", sign, whole, fraction)
+            }
+            Value::Float(float) => write!(f, "{}", float),
+            Value::Complex(complex) => {
+                let sign = if complex.im.is_sign_negative() {
+                    '-'
+                } else {
+                    '+'
+                };
+                write!(f, "{} {} {}i", complex.re, sign, complex.im.abs())
+            }
+            Value::Quantity(magnitude, unit) => write!(f, "{} {}", magnitude, unit.name),
+            Value::Function(closure) => write!(f, "{}", closure),
+            Value::List(items) => {
+                write!(f, "[")?;
+                fmt_list(f, items)?;
+                write!(f, "]")
+            }
+            Value::Matrix(matrix) => write!(f, "{}", matrix),
+        }
+    }
+}